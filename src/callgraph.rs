@@ -0,0 +1,165 @@
+use crate::parser::AstNode;
+
+/// Builds the `--callgraph` report: one line per top-level function listing the
+/// functions it calls, in source order, followed by the functions nothing calls.
+/// Builtins are left out. Calls made from nested functions are attributed to the
+/// enclosing top-level function.
+pub fn report(program: &AstNode, entry: &str) -> String {
+    let AstNode::Program(items) = program else {
+        return String::new();
+    };
+
+    let functions: Vec<(&str, &AstNode)> = items
+        .iter()
+        .filter_map(|item| match item {
+            AstNode::FunctionDef { name, body, .. } => Some((name.as_str(), body.as_ref())),
+            _ => None,
+        })
+        .collect();
+    let is_function = |name: &str| functions.iter().any(|(function, _)| *function == name);
+
+    let mut output = String::new();
+    let mut called = Vec::new();
+    for (name, body) in &functions {
+        let mut callees = Vec::new();
+        collect_calls(body, &mut callees);
+        callees.retain(|callee| is_function(callee));
+
+        if callees.is_empty() {
+            output.push_str(&format!("{}\n", name));
+        } else {
+            output.push_str(&format!("{} -> {}\n", name, callees.join(", ")));
+        }
+        for callee in callees {
+            if callee != *name && !called.contains(&callee) {
+                called.push(callee);
+            }
+        }
+    }
+
+    // Only calls from other functions count: a function that just calls itself is still dead
+    let never_called: Vec<&str> = functions
+        .iter()
+        .map(|(name, _)| *name)
+        .filter(|name| *name != entry && !called.iter().any(|callee| callee == name))
+        .collect();
+    if !never_called.is_empty() {
+        output.push_str(&format!("\nNever called: {}\n", never_called.join(", ")));
+    }
+    output
+}
+
+/// Appends the name of every function `node` calls, once each, in the order first seen.
+fn collect_calls(node: &AstNode, calls: &mut Vec<String>) {
+    let add = |name: &str, calls: &mut Vec<String>| {
+        if !calls.iter().any(|call| call == name) {
+            calls.push(name.to_string());
+        }
+    };
+
+    match node {
+        AstNode::Call { name, args, .. } => {
+            // `bench(n, "f")` names the function it runs
+            if name == "bench"
+                && let Some(AstNode::StringLit(target)) = args.get(1)
+            {
+                add(target, calls);
+            }
+            add(name, calls);
+            for arg in args {
+                collect_calls(arg, calls);
+            }
+        }
+        AstNode::Program(nodes) | AstNode::Block(nodes) | AstNode::ArrayLit(nodes) => {
+            for node in nodes {
+                collect_calls(node, calls);
+            }
+        }
+        AstNode::FunctionDef { body, .. } => collect_calls(body, calls),
+        AstNode::LetBinding { value, .. }
+        | AstNode::LetPattern { value, .. }
+        | AstNode::Assignment { value, .. }
+        | AstNode::Reference { value, .. }
+        | AstNode::Try(value)
+        | AstNode::UnaryOp { operand: value, .. }
+        | AstNode::MemberAccess { object: value, .. }
+        | AstNode::EnumValue { value: Some(value), .. }
+        | AstNode::Return(Some(value))
+        | AstNode::ExpressionStatement(value) => collect_calls(value, calls),
+        AstNode::StructInit { fields, .. } => {
+            for (_, value) in fields {
+                collect_calls(value, calls);
+            }
+        }
+        AstNode::Index { array: left, index: right }
+        | AstNode::Range { start: left, end: right }
+        | AstNode::ArrayAssignment { index: left, value: right, .. }
+        | AstNode::BinaryOp { left, right, .. } => {
+            collect_calls(left, calls);
+            collect_calls(right, calls);
+        }
+        AstNode::IfExpr { condition, then_value, else_value } => {
+            collect_calls(condition, calls);
+            collect_calls(then_value, calls);
+            collect_calls(else_value, calls);
+        }
+        AstNode::MethodCall { object, args, .. } => {
+            collect_calls(object, calls);
+            for arg in args {
+                collect_calls(arg, calls);
+            }
+        }
+        AstNode::If { condition: head, then_block, else_block }
+        | AstNode::IfLet { value: head, then_block, else_block, .. }
+        | AstNode::While { condition: head, body: then_block, else_block } => {
+            collect_calls(head, calls);
+            collect_calls(then_block, calls);
+            if let Some(else_block) = else_block {
+                collect_calls(else_block, calls);
+            }
+        }
+        AstNode::For { iterator, body, .. } => {
+            collect_calls(iterator, calls);
+            collect_calls(body, calls);
+        }
+        AstNode::Match { value, arms } => {
+            collect_calls(value, calls);
+            for arm in arms {
+                collect_calls(&arm.body, calls);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn callgraph(source: &str) -> String {
+        let tokens = Lexer::new(source, "test.brn").tokenize().unwrap();
+        report(&Parser::new(tokens, "test.brn").parse().unwrap(), "main")
+    }
+
+    #[test]
+    fn edges_and_uncalled_functions() {
+        let source = "fn helper() -> int {\nreturn 1;\n}\n\
+            fn unused() {\nprintln(helper());\n}\n\
+            fn main() {\nprintln(helper() + helper());\n}\n";
+        assert_eq!(callgraph(source), "helper\nunused -> helper\nmain -> helper\n\nNever called: unused\n");
+    }
+
+    #[test]
+    fn self_recursion_does_not_count_as_a_caller() {
+        let source = "fn spin(n: int) {\nspin(n);\n}\nfn main() {}\n";
+        assert_eq!(callgraph(source), "spin -> spin\nmain\n\nNever called: spin\n");
+    }
+
+    #[test]
+    fn benched_functions_count_as_called() {
+        let source = "fn noop() {}\nfn main() {\nlet t = bench(10, \"noop\");\nprintln(t);\n}\n";
+        assert_eq!(callgraph(source), "noop\nmain -> noop\n");
+    }
+}
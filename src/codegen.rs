@@ -1,21 +1,72 @@
-use crate::parser::{AstNode, BinOp, Parameter, Pattern};
-use std::collections::HashMap;
+use crate::format::{parse_format_string, FormatPiece};
+use crate::parser::{Location, AstNode, BinOp, EnumVariant, Field, Parameter, Pattern, UnOp};
+use crate::semantic::effective_return_type;
+use std::collections::{HashMap, HashSet};
+
+#[derive(Default)]
+pub struct CodegenOptions {
+    /// Replace `main` with a harness that runs every zero-argument `test_*` function.
+    pub test_mode: bool,
+    /// Reuse a variable's last loaded register within a basic block instead of
+    /// emitting a fresh `load` for every use.
+    pub optimize: bool,
+    /// Guard array indexing, `read_file` results and `+`/`-`/`*` on ints with
+    /// runtime checks that print the source position and exit on failure.
+    pub sanitize: bool,
+    /// Precede each statement's instructions with a `; line N: ...` comment.
+    pub annotate: bool,
+    /// Function emitted as the program's `main` symbol; `None` means `main` itself.
+    pub entry: Option<String>,
+}
 
 pub struct CodeGenerator {
+    options: CodegenOptions,
     output: String,
     string_counter: usize,
     temp_counter: usize,
+    /// Numbers the named `%slotN` stack slots; see `hoist_allocas`.
+    slot_counter: usize,
     label_counter: usize,
     string_literals: Vec<(String, String)>,
     current_function_vars: HashMap<String, VarMetadata>,
+    /// Slots of heap variables whose value was moved into another `let`, which
+    /// frees it instead.
+    moved_slots: HashSet<String>,
     loop_stack: Vec<LoopLabels>,
-    enum_types: HashMap<String, Vec<String>>,
+    enum_types: HashMap<String, Vec<EnumVariant>>,
+    struct_types: HashMap<String, Vec<Field>>,
+    /// Struct name -> the LLVM pointer type its values are passed around as.
+    struct_pointer_types: HashMap<String, String>,
     block_terminated: bool,
     current_function_name: String,
     current_function_return_type: String,
     function_signatures: HashMap<String, String>,
+    /// Declared (source-level) return type of each generated function.
+    function_return_types: HashMap<String, String>,
+    /// Declared parameters of each function, by symbol. Calls convert scalar
+    /// arguments to the declared types and fill in left-out trailing defaults.
+    function_params: HashMap<String, Vec<Parameter>>,
+    /// Scopes of nested functions visible from the current block: source name -> mangled name.
+    function_aliases: Vec<HashMap<String, String>>,
+    /// Nested functions waiting to be generated once the enclosing function is finished,
+    /// with the aliases that were visible where they were defined.
+    pending_functions: Vec<PendingFunction>,
+    /// Pointer -> register holding its most recent load in the current basic block.
+    load_cache: HashMap<String, String>,
+    /// Position of the most recently generated node that records one.
+    current_location: Location,
+    /// Set while generating a function that calls itself in tail position.
+    tail_call: Option<TailCallTarget>,
+    /// Label of the basic block instructions are currently emitted into.
+    current_block: String,
 }
 
+/// How `print` shows a float: up to 15 significant digits, so `0.1 + 0.2` prints
+/// as `0.3` and `1e8` as `100000000`. Larger values switch to an exponent (`1e+20`),
+/// `-0.0` prints as `-0`, infinities as `inf` and `-inf`, and NaN always as `nan`
+/// (see `gen_float_for_display`).
+const FLOAT_FORMAT: &str = "%.15g";
+
 #[derive(Clone)]
 struct VarMetadata {
     llvm_name: String,
@@ -23,6 +74,25 @@ struct VarMetadata {
     is_heap: bool,
     array_size: Option<usize>,
     is_string_literal: bool,
+    /// Source type of a fixed-size array's elements when they are bools or chars,
+    /// which are widened to `i64` in the array and narrowed again when read.
+    element_type: Option<String>,
+}
+
+struct PendingFunction {
+    mangled_name: String,
+    definition: AstNode,
+    aliases: Vec<HashMap<String, String>>,
+}
+
+/// Where a self tail call in the current function jumps back to, and the
+/// parameter slots it rebinds first.
+#[derive(Clone)]
+struct TailCallTarget {
+    symbol: String,
+    /// (alloca, LLVM type) of each parameter, in order
+    params: Vec<(String, String)>,
+    label: String,
 }
 
 struct LoopLabels {
@@ -31,30 +101,83 @@ struct LoopLabels {
 }
 
 impl CodeGenerator {
-    pub fn new() -> Self {
+    pub fn new(options: CodegenOptions) -> Self {
         CodeGenerator {
+            options,
             output: String::new(),
             string_counter: 0,
             temp_counter: 0,
+            slot_counter: 0,
             label_counter: 0,
             string_literals: Vec::new(),
             current_function_vars: HashMap::new(),
+            moved_slots: HashSet::new(),
             loop_stack: Vec::new(),
-            enum_types: HashMap::new(),
+            enum_types: Self::builtin_enums(),
+            struct_types: HashMap::new(),
+            struct_pointer_types: HashMap::new(),
             block_terminated: false,
             current_function_name: String::new(),
             current_function_return_type: String::new(),
             function_signatures: HashMap::new(),
+            function_return_types: HashMap::new(),
+            function_params: HashMap::new(),
+            function_aliases: Vec::new(),
+            pending_functions: Vec::new(),
+            load_cache: HashMap::new(),
+            current_location: Location { line: 1, column: 1 },
+            tail_call: None,
+            current_block: String::new(),
         }
     }
 
+    /// Enums every program can use without declaring them.
+    fn builtin_enums() -> HashMap<String, Vec<EnumVariant>> {
+        let option = vec![
+            EnumVariant { name: "Some".to_string(), value_type: Some("int".to_string()) },
+            EnumVariant { name: "None".to_string(), value_type: None },
+        ];
+        HashMap::from([("Option".to_string(), option)])
+    }
+
     pub fn generate(&mut self, ast: &AstNode) -> String {
         self.emit_header();
 
         if let AstNode::Program(nodes) = ast {
+            // Enum and struct types and function signatures are registered up front, so
+            // functions can use types and call functions defined later in the file.
+            // Enums go first since struct fields may have enum types.
+            for node in nodes {
+                if let AstNode::EnumDef { .. } = node {
+                    self.gen_node(node);
+                }
+            }
+            for node in nodes {
+                if let AstNode::StructDef { .. } = node {
+                    self.gen_node(node);
+                }
+            }
+            for node in nodes {
+                if let AstNode::FunctionDef { name, params, body, return_type, .. } = node {
+                    let symbol = self.symbol_name(name);
+                    self.register_signature(&symbol, &effective_return_type(return_type, params, body));
+                    self.function_params.insert(symbol, params.clone());
+                }
+            }
+
             for node in nodes {
+                if self.options.test_mode
+                    && let AstNode::FunctionDef { name, .. } = node
+                    && name == self.entry_name()
+                {
+                    continue;
+                }
                 self.gen_node(node);
             }
+
+            if self.options.test_mode {
+                self.gen_test_harness(nodes);
+            }
         }
 
         self.emit_footer();
@@ -69,12 +192,78 @@ impl CodeGenerator {
         self.emit("declare i64 @strlen(i8*)");
         self.emit("declare i32 @printf(i8*, ...)");
         self.emit("declare i32 @sprintf(i8*, i8*, ...)");
+        self.emit("declare i32 @snprintf(i8*, i64, i8*, ...)");
         self.emit("declare i8* @fopen(i8*, i8*)");
         self.emit("declare i32 @fclose(i8*)");
         self.emit("declare i64 @fread(i8*, i64, i64, i8*)");
         self.emit("declare i64 @fwrite(i8*, i64, i64, i8*)");
         self.emit("declare i32 @fseek(i8*, i64, i32)");
         self.emit("declare i64 @ftell(i8*)");
+        self.emit("declare i32 @strcmp(i8*, i8*)");
+        self.emit("declare void @exit(i32)");
+        self.emit("declare i64 @clock()");
+        self.emit("declare double @llvm.pow.f64(double, double)");
+        self.emit("declare i32 @_setjmp(i8*) returns_twice");
+        self.emit("declare void @longjmp(i8*, i32)");
+        self.emit("");
+
+        if self.options.sanitize {
+            self.emit("declare { i64, i1 } @llvm.sadd.with.overflow.i64(i64, i64)");
+            self.emit("declare { i64, i1 } @llvm.ssub.with.overflow.i64(i64, i64)");
+            self.emit("declare { i64, i1 } @llvm.smul.with.overflow.i64(i64, i64)");
+            self.emit("");
+            self.emit("define void @sanitize_fail(i8* %message) {");
+            self.emit("  call i32 @puts(i8* %message)");
+            self.emit("  call void @exit(i32 101)");
+            self.emit("  unreachable");
+            self.emit("}");
+            self.emit("");
+        }
+
+        self.emit("@.test_mode = global i1 0");
+        self.emit("@.test_jmp = global [256 x i8] zeroinitializer, align 16");
+        self.emit("@.test_passed = global i64 0");
+        self.emit("@.test_failed = global i64 0");
+        self.emit("");
+
+        self.emit("define i64 @pow_impl(i64 %base, i64 %exp) {");
+        self.emit("entry:");
+        self.emit("  %is_negative = icmp slt i64 %exp, 0");
+        self.emit("  br i1 %is_negative, label %negative, label %loop");
+        // A negative exponent is 1 / base**-exp truncated toward zero: 1 for base 1,
+        // +-1 for base -1 depending on the exponent's parity, and 0 otherwise
+        self.emit("negative:");
+        self.emit("  %is_one = icmp eq i64 %base, 1");
+        self.emit("  %is_minus_one = icmp eq i64 %base, -1");
+        self.emit("  %exp_bit = and i64 %exp, 1");
+        self.emit("  %exp_odd = icmp ne i64 %exp_bit, 0");
+        self.emit("  %minus_one_power = select i1 %exp_odd, i64 -1, i64 1");
+        self.emit("  %one_or_zero = select i1 %is_one, i64 1, i64 0");
+        self.emit("  %fraction = select i1 %is_minus_one, i64 %minus_one_power, i64 %one_or_zero");
+        self.emit("  ret i64 %fraction");
+        self.emit("loop:");
+        self.emit("  %result = phi i64 [ 1, %entry ], [ %next_result, %loop ]");
+        self.emit("  %count = phi i64 [ %exp, %entry ], [ %next_count, %loop ]");
+        self.emit("  %done = icmp eq i64 %count, 0");
+        self.emit("  %next_result = mul i64 %result, %base");
+        self.emit("  %next_count = sub i64 %count, 1");
+        self.emit("  br i1 %done, label %exit, label %loop");
+        self.emit("exit:");
+        self.emit("  ret i64 %result");
+        self.emit("}");
+        self.emit("");
+
+        self.emit("define void @assert_fail_impl() noreturn {");
+        self.emit("  %in_test = load i1, i1* @.test_mode");
+        self.emit("  br i1 %in_test, label %unwind, label %abort");
+        self.emit("unwind:");
+        self.emit("  %buf = getelementptr inbounds [256 x i8], [256 x i8]* @.test_jmp, i64 0, i64 0");
+        self.emit("  call void @longjmp(i8* %buf, i32 1)");
+        self.emit("  unreachable");
+        self.emit("abort:");
+        self.emit("  call void @exit(i32 1)");
+        self.emit("  unreachable");
+        self.emit("}");
         self.emit("");
 
         self.emit("define i8* @read_file_impl(i8* %filename) {");
@@ -129,29 +318,90 @@ impl CodeGenerator {
     }
 
     fn gen_node(&mut self, node: &AstNode) -> String {
+        if let Some(location) = node.location() {
+            self.current_location = location;
+        }
+
         match node {
             AstNode::EnumDef { name, variants } => {
-                let variant_names: Vec<String> = variants.iter().map(|v| v.name.clone()).collect();
-                self.enum_types.insert(name.clone(), variant_names);
+                self.enum_types.insert(name.clone(), variants.clone());
                 "0".to_string()
             }
 
-            AstNode::EnumValue { enum_name, variant, value } => {
-                let tag = if let Some(variants) = self.enum_types.get(enum_name) {
-                    variants.iter().position(|v| v == variant).unwrap_or(0) as i64
-                } else {
-                    0
+            AstNode::StructDef { name, fields } => {
+                if self.struct_types.contains_key(name) {
+                    return "0".to_string();
+                }
+                self.struct_types.insert(name.clone(), fields.clone());
+                self.struct_pointer_types.insert(name.clone(), format!("%struct.{}*", name));
+
+                let field_types: Vec<String> = fields
+                    .iter()
+                    .map(|field| self.type_to_llvm(&field.field_type).to_string())
+                    .collect();
+                // Type definitions live at module level, wherever the struct was declared
+                self.output = format!("%struct.{} = type {{ {} }}\n{}", name, field_types.join(", "), self.output);
+                "0".to_string()
+            }
+
+            AstNode::StructInit { name, fields } => {
+                let Some(definition) = self.struct_types.get(name).cloned() else {
+                    eprintln!("CODEGEN ERROR: Struct '{}' not found!", name);
+                    return "0".to_string();
+                };
+
+                let ptr = self.new_slot();
+                self.emit(&format!("  {} = alloca %struct.{}", ptr, name));
+                for (field_name, value) in fields {
+                    let Some(index) = definition.iter().position(|field| &field.name == field_name) else {
+                        continue;
+                    };
+                    let value_reg = self.gen_node(value);
+                    let field_llvm = self.type_to_llvm(&definition[index].field_type).to_string();
+                    let field_ptr = self.new_temp();
+                    self.emit(&format!("  {} = getelementptr %struct.{}, %struct.{}* {}, i32 0, i32 {}",
+                        field_ptr, name, name, ptr, index));
+                    self.emit(&format!("  store {} {}, {}* {}", field_llvm, value_reg, field_llvm, field_ptr));
+                }
+                ptr
+            }
+
+            AstNode::MemberAccess { object, field } => {
+                let struct_name = self.infer_llvm_type(object);
+                let Some((index, field_type)) = self.struct_field(&struct_name, field) else {
+                    eprintln!("CODEGEN ERROR: Struct '{}' has no field '{}'!", struct_name, field);
+                    return "0".to_string();
                 };
 
+                let object_reg = self.gen_node(object);
+                let field_llvm = self.type_to_llvm(&field_type).to_string();
+                let field_ptr = self.new_temp();
+                self.emit(&format!("  {} = getelementptr %struct.{}, %struct.{}* {}, i32 0, i32 {}",
+                    field_ptr, struct_name, struct_name, object_reg, index));
+                let result = self.new_temp();
+                self.emit(&format!("  {} = load {}, {}* {}", result, field_llvm, field_llvm, field_ptr));
+                result
+            }
+
+            AstNode::SizeOf(type_name) => self.size_of_type(type_name).to_string(),
+
+            AstNode::EnumValue { enum_name, variant, value } => {
+                let tag = self.variant_tag(enum_name, variant);
+
+                // Heap-allocated so enum values can be returned from functions
+                let raw = self.new_temp();
+                self.emit(&format!("  {} = call i8* @malloc(i64 16)", raw));
                 let ptr = self.new_temp();
-                self.emit(&format!("  {} = alloca {{ i32, i64 }}", ptr));
+                self.emit(&format!("  {} = bitcast i8* {} to {{ i32, i64 }}*", ptr, raw));
 
                 let tag_ptr = self.new_temp();
                 self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 0", tag_ptr, ptr));
                 self.emit(&format!("  store i32 {}, i32* {}", tag, tag_ptr));
 
                 let val = if let Some(v) = value {
-                    self.gen_node(v)
+                    let reg = self.gen_node(v);
+                    let value_type = self.infer_llvm_type(v);
+                    self.pack_payload(&reg, &value_type)
                 } else {
                     "0".to_string()
                 };
@@ -163,14 +413,62 @@ impl CodeGenerator {
                 ptr
             }
 
-            AstNode::Match { value, arms } => {
-                let value_reg = self.gen_node(value);
-                let end_label = self.new_label("match_end");
+            AstNode::Try(expr) => {
+                let value_reg = self.gen_node(expr);
+                let err_tag = self.enum_types.get("Result")
+                    .and_then(|variants| variants.iter().position(|v| v.name == "Err"))
+                    .unwrap_or(1);
 
                 let tag_ptr = self.new_temp();
                 self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 0", tag_ptr, value_reg));
                 let tag = self.new_temp();
                 self.emit(&format!("  {} = load i32, i32* {}", tag, tag_ptr));
+                let is_err = self.new_temp();
+                self.emit(&format!("  {} = icmp eq i32 {}, {}", is_err, tag, err_tag));
+
+                let err_label = self.new_label("try_err");
+                let ok_label = self.new_label("try_ok");
+                self.emit(&format!("  br i1 {}, label %{}, label %{}", is_err, err_label, ok_label));
+
+                // The error is returned as it is, so the caller now owns it
+                self.emit(&format!("{}:", err_label));
+                let err_reg = self.gen_returned_enum(expr, &value_reg);
+                let returned: &[&str] = match expr.as_ref() {
+                    AstNode::Identifier { name, .. } => &[name.as_str()],
+                    _ => &[],
+                };
+                self.gen_free_heap_vars(returned);
+                let ret_type = self.current_function_return_type.clone();
+                self.emit(&format!("  ret {} {}", ret_type, err_reg));
+
+                self.emit(&format!("{}:", ok_label));
+                let payload_ptr = self.new_temp();
+                self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 1", payload_ptr, value_reg));
+                let payload = self.new_temp();
+                self.emit(&format!("  {} = load i64, i64* {}", payload, payload_ptr));
+                // Nothing else refers to a `Result` made just for the `?`
+                if matches!(expr.as_ref(), AstNode::Call { .. } | AstNode::EnumValue { .. }) {
+                    let raw = self.new_temp();
+                    self.emit(&format!("  {} = bitcast {{ i32, i64 }}* {} to i8*", raw, value_reg));
+                    self.emit(&format!("  call void @free(i8* {})", raw));
+                }
+                payload
+            }
+
+            AstNode::Match { value, arms } => {
+                let value_reg = self.gen_node(value);
+                let end_label = self.new_label("match_end");
+
+                // Only enum scrutinees have a tag; strings are matched with strcmp
+                let tag = if arms.iter().any(|arm| matches!(arm.pattern, Pattern::EnumPattern { .. })) {
+                    let tag_ptr = self.new_temp();
+                    self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 0", tag_ptr, value_reg));
+                    let tag = self.new_temp();
+                    self.emit(&format!("  {} = load i32, i32* {}", tag, tag_ptr));
+                    tag
+                } else {
+                    String::new()
+                };
 
                 for (i, arm) in arms.iter().enumerate() {
                     let arm_label = self.new_label(&format!("match_arm_{}", i));
@@ -181,8 +479,8 @@ impl CodeGenerator {
                     };
 
                     match &arm.pattern {
-                        Pattern::EnumPattern { variant, binding, .. } => {
-                            let variant_tag = i as i32;
+                        Pattern::EnumPattern { enum_name, variant, binding } => {
+                            let variant_tag = self.variant_tag(enum_name, variant);
                             let cond = self.new_temp();
                             self.emit(&format!("  {} = icmp eq i32 {}, {}", cond, tag, variant_tag));
                             self.emit(&format!("  br i1 {}, label %{}, label %{}", cond, arm_label, next_label));
@@ -190,38 +488,31 @@ impl CodeGenerator {
                             self.emit(&format!("{}:", arm_label));
 
                             if let Some(binding) = binding {
-                                let val_ptr = self.new_temp();
-                                self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 1", val_ptr, value_reg));
-                                let val = self.new_temp();
-                                self.emit(&format!("  {} = load i64, i64* {}", val, val_ptr));
-
-                                let var_ptr = self.new_temp();
-                                self.emit(&format!("  {} = alloca i64", var_ptr));
-                                self.emit(&format!("  store i64 {}, i64* {}", val, var_ptr));
-
-                                self.current_function_vars.insert(binding.clone(), VarMetadata {
-                                    llvm_name: var_ptr,
-                                    var_type: "int".to_string(),
-                                    is_heap: false,
-                                    array_size: None,
-                                    is_string_literal: false,
-                                });
+                                self.bind_enum_payload(&value_reg, &arm.pattern, binding);
                             }
 
-                            self.gen_node(&arm.body);
-                            self.emit(&format!("  br label %{}", end_label));
+                            self.gen_match_arm_body(&arm.body, &end_label);
+                        }
+                        Pattern::StringLiteral(literal) => {
+                            let literal_ptr = self.gen_string_ptr(literal);
+                            let cmp = self.new_temp();
+                            self.emit(&format!("  {} = call i32 @strcmp(i8* {}, i8* {})", cmp, value_reg, literal_ptr));
+                            let cond = self.new_temp();
+                            self.emit(&format!("  {} = icmp eq i32 {}, 0", cond, cmp));
+                            self.emit(&format!("  br i1 {}, label %{}, label %{}", cond, arm_label, next_label));
+
+                            self.emit(&format!("{}:", arm_label));
+                            self.gen_match_arm_body(&arm.body, &end_label);
                         }
                         Pattern::Wildcard => {
                             self.emit(&format!("  br label %{}", arm_label));
                             self.emit(&format!("{}:", arm_label));
-                            self.gen_node(&arm.body);
-                            self.emit(&format!("  br label %{}", end_label));
+                            self.gen_match_arm_body(&arm.body, &end_label);
                         }
                         Pattern::Identifier(_) => {
                             self.emit(&format!("  br label %{}", arm_label));
                             self.emit(&format!("{}:", arm_label));
-                            self.gen_node(&arm.body);
-                            self.emit(&format!("  br label %{}", end_label));
+                            self.gen_match_arm_body(&arm.body, &end_label);
                         }
                     }
 
@@ -231,27 +522,85 @@ impl CodeGenerator {
                 }
 
                 self.emit(&format!("{}:", end_label));
+                self.block_terminated = false;
                 "0".to_string()
             }
 
-            AstNode::FunctionDef { name, params, body, return_type } => {
-                self.gen_function(name, params, body, return_type)
+            AstNode::FunctionDef { name, params, body, return_type, .. } => {
+                self.gen_function(name, params, body, return_type);
+
+                // Nested functions are hoisted out as `outer.inner` after their parent
+                while let Some(pending) = self.pending_functions.pop() {
+                    if let AstNode::FunctionDef { params, body, return_type, .. } = &pending.definition {
+                        let outer_aliases = std::mem::replace(&mut self.function_aliases, pending.aliases);
+                        self.gen_function(&pending.mangled_name, params, body, return_type);
+                        self.function_aliases = outer_aliases;
+                    }
+                }
+                String::new()
             }
 
-            AstNode::LetBinding { name, value, .. } => {
-                let value_reg = self.gen_node(value);
-                let var_type = self.infer_llvm_type(value);
+            AstNode::LetBinding { name, value, type_annotation, .. } => {
+                let mut value_reg = self.gen_node(value);
+                let mut var_type = self.infer_llvm_type(value);
+                // `let x: float = 3;` stores a double, not the int it was written as
+                if let Some(annotation) = type_annotation
+                    && is_scalar(annotation)
+                    && is_scalar(&var_type)
+                {
+                    value_reg = self.coerce(&value_reg, &var_type, annotation);
+                    var_type = annotation.clone();
+                }
 
                 let is_string_literal = matches!(value.as_ref(), AstNode::StringLit(_));
-                let is_heap = var_type == "string" && !is_string_literal;
+                // `let b = a;` takes over `a`'s allocation, if it has one
+                let moved_from = match value.as_ref() {
+                    AstNode::Identifier { name, .. } => self.current_function_vars
+                        .get(name)
+                        .filter(|meta| meta.is_heap && !self.moved_slots.contains(&meta.llvm_name))
+                        .map(|meta| meta.llvm_name.clone()),
+                    _ => None,
+                };
+                let is_heap = match value.as_ref() {
+                    AstNode::Identifier { .. } => moved_from.is_some(),
+                    // Elements of a string array (e.g. `args[0]`) are owned by the array
+                    _ if matches!(var_type.as_str(), "string" | "[char]") => {
+                        !is_string_literal && !matches!(value.as_ref(), AstNode::Index { .. })
+                    }
+                    // Enum fields and elements stay with their container
+                    _ if self.is_enum_type(&var_type) => {
+                        matches!(value.as_ref(), AstNode::EnumValue { .. } | AstNode::Call { .. })
+                    }
+                    _ => false,
+                };
+                if let Some(slot) = moved_from {
+                    self.moved_slots.insert(slot);
+                }
 
-                let array_size = if let AstNode::ArrayLit(elements) = value.as_ref() {
-                    Some(elements.len())
-                } else {
-                    None
+                let array_size = match value.as_ref() {
+                    AstNode::ArrayLit(elements) => Some(elements.len()),
+                    AstNode::Identifier { name, .. } => {
+                        self.current_function_vars.get(name).and_then(|meta| meta.array_size)
+                    }
+                    _ => None,
                 };
 
-                let ptr = self.new_temp();
+                // A fixed-size array already lives in its own `[N x i64]` alloca;
+                // the variable names that allocation directly
+                if let Some(size) = array_size {
+                    let element_type = self.array_element_type(value);
+                    self.current_function_vars.insert(name.clone(), VarMetadata {
+                        llvm_name: value_reg.clone(),
+                        var_type,
+                        is_heap: false,
+                        array_size: Some(size),
+                        is_string_literal: false,
+                        element_type,
+                    });
+                    return value_reg;
+                }
+
+                let ptr = self.new_slot();
                 let llvm_type_str = self.type_to_llvm(&var_type).to_string();
                 self.emit(&format!("  {} = alloca {}", ptr, llvm_type_str));
                 self.emit(&format!("  store {} {}, {}* {}", llvm_type_str, value_reg, llvm_type_str, ptr));
@@ -262,17 +611,42 @@ impl CodeGenerator {
                     is_heap,
                     array_size,
                     is_string_literal,
+                    element_type: None,
                 });
 
                 ptr
             }
 
+            AstNode::LetPattern { pattern, value, .. } => {
+                // Semantic analysis only lets through patterns that always match,
+                // so there is no tag to check
+                let value_reg = self.gen_node(value);
+                if let Pattern::EnumPattern { binding: Some(binding), .. } = pattern {
+                    self.bind_enum_payload(&value_reg, pattern, binding);
+                }
+                value_reg
+            }
+
             AstNode::ArrayAssignment { array, index, value, .. } => {
                 let index_val = self.gen_node(index);
                 let value_reg = self.gen_node(value);
 
-                if let Some(meta) = self.current_function_vars.get(array).cloned() {
+                if let Some(meta) = self.current_function_vars.get(array).cloned()
+                    && meta.var_type == "[char]"
+                {
+                    let base = self.new_temp();
+                    self.emit(&format!("  {} = load i8*, i8** {}", base, meta.llvm_name));
+                    self.gen_chars_bounds_check(&base, &index_val);
+                    let elem_ptr = self.new_temp();
+                    self.emit(&format!("  {} = getelementptr i8, i8* {}, i64 {}", elem_ptr, base, index_val));
+                    self.emit(&format!("  store i8 {}, i8* {}", value_reg, elem_ptr));
+                } else if let Some(meta) = self.current_function_vars.get(array).cloned() {
+                    if let Some(size) = meta.array_size {
+                        self.gen_bounds_check(&index_val, &size.to_string());
+                    }
                     let array_size = meta.array_size.unwrap_or(100);
+                    let value_type = self.infer_llvm_type(value);
+                    let value_reg = self.coerce(&value_reg, &value_type, "int");
                     let elem_ptr = self.new_temp();
                     self.emit(&format!("  {} = getelementptr [{} x i64], [{} x i64]* {}, i64 0, i64 {}", 
                         elem_ptr, array_size, array_size, meta.llvm_name, index_val));
@@ -283,9 +657,13 @@ impl CodeGenerator {
             }
 
             AstNode::Assignment { name, value, .. } => {
-                let value_reg = self.gen_node(value);
+                let mut value_reg = self.gen_node(value);
 
                 if let Some(meta) = self.current_function_vars.get(name).cloned() {
+                    let value_type = self.infer_llvm_type(value);
+                    if is_scalar(&meta.var_type) && is_scalar(&value_type) {
+                        value_reg = self.coerce(&value_reg, &value_type, &meta.var_type);
+                    }
                     let llvm_type_str = self.type_to_llvm(&meta.var_type).to_string();
                     let llvm_name = meta.llvm_name.clone();
                     self.emit(&format!("  store {} {}, {}* {}", llvm_type_str, value_reg, llvm_type_str, llvm_name));
@@ -295,7 +673,7 @@ impl CodeGenerator {
             }
 
             AstNode::If { condition, then_block, else_block } => {
-                let cond_reg = self.gen_node(condition);
+                let cond_reg = self.gen_condition(condition);
                 let then_label = self.new_label("then");
                 let else_label = self.new_label("else");
                 let end_label = self.new_label("endif");
@@ -329,11 +707,103 @@ impl CodeGenerator {
                 if then_terminated && else_terminated {
                     self.emit("  unreachable");
                 }
+                self.block_terminated = then_terminated && else_terminated;
+                "0".to_string()
+            }
+
+            AstNode::IfExpr { condition, then_value, else_value } => {
+                let result_type = self.infer_llvm_type(node);
+                let then_label = self.new_label("ifv_then");
+                let else_label = self.new_label("ifv_else");
+                let end_label = self.new_label("ifv_end");
+
+                let cond_reg = self.gen_condition(condition);
+                self.emit(&format!("  br i1 {}, label %{}, label %{}", cond_reg, then_label, else_label));
+
+                let mut incoming = Vec::new();
+                for (label, value) in [(&then_label, then_value), (&else_label, else_value)] {
+                    self.emit(&format!("{}:", label));
+                    let reg = self.gen_node(value);
+                    let value_type = self.infer_llvm_type(value);
+                    let reg = if is_scalar(&value_type) && is_scalar(&result_type) {
+                        self.coerce(&reg, &value_type, &result_type)
+                    } else if result_type == "string"
+                        && matches!(
+                            value.as_ref(),
+                            AstNode::StringLit(_) | AstNode::Identifier { .. } | AstNode::Index { .. } | AstNode::MemberAccess { .. }
+                        )
+                    {
+                        // A string result is always a fresh heap copy, so whoever binds it owns it
+                        self.gen_string_copy(&reg)
+                    } else {
+                        reg
+                    };
+                    // The branch may have opened blocks of its own
+                    incoming.push(format!("[ {}, %{} ]", reg, self.current_block));
+                    self.emit(&format!("  br label %{}", end_label));
+                }
+
+                self.emit(&format!("{}:", end_label));
+                let result = self.new_temp();
+                let llvm_type = self.type_to_llvm(&result_type).to_string();
+                self.emit(&format!("  {} = phi {} {}", result, llvm_type, incoming.join(", ")));
+                result
+            }
+
+            AstNode::IfLet { pattern, value, then_block, else_block } => {
+                let value_reg = self.gen_node(value);
+                let then_label = self.new_label("iflet_then");
+                let else_label = self.new_label("iflet_else");
+                let end_label = self.new_label("iflet_end");
+
+                let Pattern::EnumPattern { enum_name, variant, binding } = pattern else {
+                    return "0".to_string();
+                };
+                let variant_tag = self.variant_tag(enum_name, variant);
+
+                let tag_ptr = self.new_temp();
+                self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 0", tag_ptr, value_reg));
+                let tag = self.new_temp();
+                self.emit(&format!("  {} = load i32, i32* {}", tag, tag_ptr));
+                let cond = self.new_temp();
+                self.emit(&format!("  {} = icmp eq i32 {}, {}", cond, tag, variant_tag));
+                let miss_label = if else_block.is_some() { &else_label } else { &end_label };
+                self.emit(&format!("  br i1 {}, label %{}, label %{}", cond, then_label, miss_label));
+
+                // The binding is only in scope for the then-block
+                self.emit(&format!("{}:", then_label));
+                let vars_before = self.current_function_vars.clone();
+                if let Some(binding) = binding {
+                    self.bind_enum_payload(&value_reg, pattern, binding);
+                }
                 self.block_terminated = false;
+                self.gen_node(then_block);
+                self.current_function_vars = vars_before;
+                let then_terminated = self.block_terminated;
+                if !self.block_terminated {
+                    self.emit(&format!("  br label %{}", end_label));
+                }
+
+                let mut else_terminated = false;
+                if let Some(else_block) = else_block {
+                    self.emit(&format!("{}:", else_label));
+                    self.block_terminated = false;
+                    self.gen_node(else_block);
+                    else_terminated = self.block_terminated;
+                    if !self.block_terminated {
+                        self.emit(&format!("  br label %{}", end_label));
+                    }
+                }
+
+                self.emit(&format!("{}:", end_label));
+                if then_terminated && else_terminated {
+                    self.emit("  unreachable");
+                }
+                self.block_terminated = then_terminated && else_terminated;
                 "0".to_string()
             }
 
-            AstNode::While { condition, body } => {
+            AstNode::While { condition, body, else_block } => {
                 let cond_label = self.new_label("while_cond");
                 let body_label = self.new_label("while_body");
                 let end_label = self.new_label("while_end");
@@ -343,10 +813,20 @@ impl CodeGenerator {
                     break_label: end_label.clone(),
                 });
 
-                self.emit(&format!("  br label %{}", cond_label));
+                let else_label = if else_block.is_some() {
+                    // The first check is emitted separately so that a false condition
+                    // can branch to the else block instead of the loop exit.
+                    let else_label = self.new_label("while_else");
+                    let first_cond = self.gen_condition(condition);
+                    self.emit(&format!("  br i1 {}, label %{}, label %{}", first_cond, body_label, else_label));
+                    Some(else_label)
+                } else {
+                    self.emit(&format!("  br label %{}", cond_label));
+                    None
+                };
 
                 self.emit(&format!("{}:", cond_label));
-                let cond_reg = self.gen_node(condition);
+                let cond_reg = self.gen_condition(condition);
                 self.emit(&format!("  br i1 {}, label %{}, label %{}", cond_reg, body_label, end_label));
 
                 self.emit(&format!("{}:", body_label));
@@ -355,60 +835,143 @@ impl CodeGenerator {
                 if !self.block_terminated {
                     self.emit(&format!("  br label %{}", cond_label));
                 }
+                self.loop_stack.pop();
+
+                if let (Some(else_label), Some(else_block)) = (else_label, else_block) {
+                    self.emit(&format!("{}:", else_label));
+                    self.block_terminated = false;
+                    self.gen_node(else_block);
+                    if !self.block_terminated {
+                        self.emit(&format!("  br label %{}", end_label));
+                    }
+                }
 
                 self.emit(&format!("{}:", end_label));
-                self.loop_stack.pop();
                 self.block_terminated = false;
                 "0".to_string()
             }
 
+            AstNode::For { variable, iterator, body } if matches!(iterator.as_ref(), AstNode::Range { .. }) => {
+                if let AstNode::Range { start, end } = iterator.as_ref() {
+                    self.gen_range_for(variable, start, end, body);
+                }
+                "0".to_string()
+            }
+
             AstNode::For { variable, iterator, body } => {
                 let start_label = self.new_label("for_start");
                 let body_label = self.new_label("for_body");
+                let step_label = self.new_label("for_step");
                 let end_label = self.new_label("for_end");
 
-                self.loop_stack.push(LoopLabels {
-                    continue_label: start_label.clone(),
-                    break_label: end_label.clone(),
-                });
-
-                let loop_var = self.new_temp();
-                self.emit(&format!("  {} = alloca i64", loop_var));
-                self.emit(&format!("  store i64 0, i64* {}", loop_var));
+                // A literal is built once up front and a named array is indexed in
+                // place; any other expression is stored in a hidden variable first
+                let literal = match iterator.as_ref() {
+                    AstNode::ArrayLit(elements) if !elements.is_empty() => {
+                        Some((self.gen_node(iterator), elements.len()))
+                    }
+                    _ => None,
+                };
+                let iterator = match iterator.as_ref() {
+                    AstNode::ArrayLit(_) | AstNode::Identifier { .. } => iterator.as_ref().clone(),
+                    _ => {
+                        let name = format!("{}.items", variable);
+                        self.gen_node(&AstNode::LetBinding {
+                            mutable: false,
+                            name: name.clone(),
+                            type_annotation: None,
+                            value: iterator.clone(),
+                            location: self.current_location,
+                        });
+                        AstNode::Identifier { name, location: self.current_location }
+                    }
+                };
+                let bound = self.gen_len(&iterator);
 
-                self.current_function_vars.insert(variable.clone(), VarMetadata {
-                    llvm_name: loop_var.clone(),
+                // The loop variable and index are only in scope inside the loop
+                let vars_before = self.current_function_vars.clone();
+                let index_var = self.new_slot();
+                self.emit(&format!("  {} = alloca i64", index_var));
+                self.emit(&format!("  store i64 0, i64* {}", index_var));
+                let index_name = format!("{}.index", variable);
+                self.current_function_vars.insert(index_name.clone(), VarMetadata {
+                    llvm_name: index_var.clone(),
                     var_type: "int".to_string(),
                     is_heap: false,
                     array_size: None,
-                    is_string_literal: false
+                    is_string_literal: false,
+                    element_type: None,
+                });
+
+                let element = AstNode::Index {
+                    array: Box::new(iterator),
+                    index: Box::new(AstNode::Identifier {
+                        name: index_name,
+                        location: self.current_location,
+                    }),
+                };
+                let element_type = self.infer_llvm_type(&element);
+                let element_llvm = self.type_to_llvm(&element_type).to_string();
+                let loop_var = self.new_slot();
+                self.emit(&format!("  {} = alloca {}", loop_var, element_llvm));
+
+                self.loop_stack.push(LoopLabels {
+                    continue_label: step_label.clone(),
+                    break_label: end_label.clone(),
                 });
 
                 self.emit(&format!("  br label %{}", start_label));
 
                 self.emit(&format!("{}:", start_label));
                 let current = self.new_temp();
-                self.emit(&format!("  {} = load i64, i64* {}", current, loop_var));
+                self.emit(&format!("  {} = load i64, i64* {}", current, index_var));
                 let cond = self.new_temp();
-                self.emit(&format!("  {} = icmp slt i64 {}, 10", cond, current));
+                self.emit(&format!("  {} = icmp slt i64 {}, {}", cond, current, bound));
                 self.emit(&format!("  br i1 {}, label %{}, label %{}", cond, body_label, end_label));
 
                 self.emit(&format!("{}:", body_label));
+                let value = match &literal {
+                    Some((array_ptr, size)) => {
+                        let elem_ptr = self.new_temp();
+                        self.emit(&format!("  {} = getelementptr [{} x i64], [{} x i64]* {}, i64 0, i64 {}",
+                            elem_ptr, size, size, array_ptr, current));
+                        let value = self.new_temp();
+                        self.emit(&format!("  {} = load i64, i64* {}", value, elem_ptr));
+                        self.coerce(&value, "int", &element_type)
+                    }
+                    None => self.gen_node(&element),
+                };
+                self.emit(&format!("  store {} {}, {}* {}", element_llvm, value, element_llvm, loop_var));
+                self.current_function_vars.insert(variable.clone(), VarMetadata {
+                    llvm_name: loop_var.clone(),
+                    var_type: element_type,
+                    is_heap: false,
+                    array_size: None,
+                    is_string_literal: false,
+                    element_type: None,
+                });
+
+                self.block_terminated = false;
                 self.gen_node(body);
+                if !self.block_terminated {
+                    self.emit(&format!("  br label %{}", step_label));
+                }
 
+                // `continue` lands here so the index still advances
+                self.emit(&format!("{}:", step_label));
                 let next = self.new_temp();
-                let curr2 = self.new_temp();
-                self.emit(&format!("  {} = load i64, i64* {}", curr2, loop_var));
-                self.emit(&format!("  {} = add i64 {}, 1", next, curr2));
-                self.emit(&format!("  store i64 {}, i64* {}", next, loop_var));
+                self.emit(&format!("  {} = add i64 {}, 1", next, current));
+                self.emit(&format!("  store i64 {}, i64* {}", next, index_var));
                 self.emit(&format!("  br label %{}", start_label));
 
                 self.emit(&format!("{}:", end_label));
+                self.block_terminated = false;
                 self.loop_stack.pop();
+                self.current_function_vars = vars_before;
                 "0".to_string()
             }
 
-            AstNode::Break => {
+            AstNode::Break { .. } => {
                 if let Some(labels) = self.loop_stack.last() {
                     let break_label = labels.break_label.clone();
                     self.emit(&format!("  br label %{}", break_label));
@@ -417,7 +980,7 @@ impl CodeGenerator {
                 "0".to_string()
             }
 
-            AstNode::Continue => {
+            AstNode::Continue { .. } => {
                 if let Some(labels) = self.loop_stack.last() {
                     let continue_label = labels.continue_label.clone();
                     self.emit(&format!("  br label %{}", continue_label));
@@ -427,10 +990,37 @@ impl CodeGenerator {
             }
 
             AstNode::Return(value) => {
+                if let Some(value) = value
+                    && let AstNode::Call { name, args, .. } = value.as_ref()
+                    && let Some(target) = self.tail_call.clone()
+                    && self.resolve_function(name) == target.symbol
+                    && args.len() == target.params.len()
+                {
+                    self.gen_tail_call(args, &target);
+                    return "0".to_string();
+                }
+
                 if let Some(value) = value {
-                    let value_reg = self.gen_node(value);
+                    let mut value_reg = self.gen_node(value);
+                    let symbol = self.symbol_name(&self.current_function_name);
+                    if let Some(declared) = self.function_return_types.get(&symbol).cloned() {
+                        let value_type = self.infer_llvm_type(value);
+                        if is_scalar(&declared) && is_scalar(&value_type) {
+                            value_reg = self.coerce(&value_reg, &value_type, &declared);
+                        }
+                    }
+                    let value_reg = self.gen_returned_enum(value, &value_reg);
+                    // The returned variable itself is handed to the caller
+                    let returned: &[&str] = match value.as_ref() {
+                        AstNode::Identifier { name, .. } => &[name.as_str()],
+                        _ => &[],
+                    };
+                    self.gen_free_heap_vars(returned);
                     let ret_type = &self.current_function_return_type.clone();
                     self.emit(&format!("  ret {} {}", ret_type, value_reg));
+                } else if self.current_function_return_type == "void" {
+                    self.gen_free_heap_vars(&[]);
+                    self.emit("  ret void");
                 } else {
                     self.emit("  ret i32 0");
                 }
@@ -442,50 +1032,141 @@ impl CodeGenerator {
                 let mut last_reg = String::new();
                 let vars_before = self.current_function_vars.clone();
 
+                let nested: HashMap<String, String> = statements
+                    .iter()
+                    .filter_map(|stmt| match stmt {
+                        AstNode::FunctionDef { name, .. } => {
+                            Some((name.clone(), format!("{}.{}", self.current_function_name, name)))
+                        }
+                        _ => None,
+                    })
+                    .collect();
+
+                if !nested.is_empty() {
+                    self.function_aliases.push(nested.clone());
+                    for stmt in statements {
+                        if let AstNode::FunctionDef { name, params, body, return_type, .. } = stmt {
+                            let mangled = nested[name].clone();
+                            self.register_signature(&mangled, &effective_return_type(return_type, params, body));
+                            self.function_params.insert(mangled.clone(), params.clone());
+                            self.pending_functions.push(PendingFunction {
+                                mangled_name: mangled,
+                                definition: stmt.clone(),
+                                aliases: self.function_aliases.clone(),
+                            });
+                        }
+                    }
+                }
+
                 for stmt in statements {
+                    if matches!(stmt, AstNode::FunctionDef { .. }) {
+                        continue;
+                    }
+                    // Nothing after a `return`, `break` or `continue` can run
+                    if self.block_terminated {
+                        break;
+                    }
+                    if self.options.annotate {
+                        self.emit_annotation(stmt);
+                    }
                     last_reg = self.gen_node(stmt);
                 }
 
-                let vars_to_free: Vec<_> = self.current_function_vars
-                    .iter()
-                    .filter(|(name, meta)| {
-                        meta.is_heap 
-                        && !meta.is_string_literal
-                        && !vars_before.contains_key(name.as_str())
-                    })
-                    .map(|(_, meta)| meta.llvm_name.clone())
-                    .collect();
+                if !nested.is_empty() {
+                    self.function_aliases.pop();
+                }
 
-                for llvm_name in vars_to_free {
-                    let ptr_reg = self.new_temp();
-                    self.emit(&format!("  {} = load i8*, i8** {}", ptr_reg, llvm_name));
-                    self.emit(&format!("  call void @free(i8* {})", ptr_reg));
+                // A `return` has already freed everything; after `break` or `continue`
+                // there is no block left to free in
+                if !self.block_terminated {
+                    let vars_to_free: Vec<_> = self.current_function_vars
+                        .iter()
+                        .filter(|(name, meta)| {
+                            meta.is_heap
+                            && !meta.is_string_literal
+                            && !self.moved_slots.contains(&meta.llvm_name)
+                            && !vars_before.contains_key(name.as_str())
+                        })
+                        .map(|(_, meta)| meta.clone())
+                        .collect();
+                    self.gen_frees(&vars_to_free);
                 }
 
+                // Variables declared in this block go out of scope with it
+                self.current_function_vars = vars_before;
+
                 last_reg
             }
 
             AstNode::ExpressionStatement(expr) => self.gen_node(expr),
 
+            AstNode::BinaryOp { op: op @ (BinOp::And | BinOp::Or), left, right } => {
+                self.gen_short_circuit(matches!(op, BinOp::And), left, right)
+            }
+
             AstNode::BinaryOp { op, left, right } => {
                 let left_reg = self.gen_node(left);
                 let right_reg = self.gen_node(right);
+                if self.infer_llvm_type(left) == "float" || self.infer_llvm_type(right) == "float" {
+                    return self.gen_float_binary(op, left, right, &left_reg, &right_reg);
+                }
+                let (left_reg, right_reg, width) = self.unify_operands(op, left, right, left_reg, right_reg);
+
+                if self.infer_llvm_type(left) == "int"
+                    && let Some(operand) = Self::arithmetic_identity(op, &left_reg, &right_reg)
+                {
+                    return operand;
+                }
+
+                let left_type = self.infer_llvm_type(left);
+                if matches!(op, BinOp::Equal | BinOp::NotEqual)
+                    && (left_type == "enum" || self.enum_types.contains_key(&left_type))
+                {
+                    return self.gen_enum_eq(&left_reg, &right_reg, matches!(op, BinOp::NotEqual));
+                }
+                // Strings compare by content, ordered byte by byte like `strcmp`
+                let string_pred = match op {
+                    BinOp::Equal => Some("eq"),
+                    BinOp::NotEqual => Some("ne"),
+                    BinOp::LessThan => Some("slt"),
+                    BinOp::LessEqual => Some("sle"),
+                    BinOp::GreaterThan => Some("sgt"),
+                    BinOp::GreaterEqual => Some("sge"),
+                    _ => None,
+                };
+                if left_type == "string"
+                    && let Some(pred) = string_pred
+                {
+                    let cmp = self.new_temp();
+                    self.emit(&format!("  {} = call i32 @strcmp(i8* {}, i8* {})", cmp, left_reg, right_reg));
+                    let result = self.new_temp();
+                    self.emit(&format!("  {} = icmp {} i32 {}, 0", result, pred, cmp));
+                    return result;
+                }
 
                 match op {
                     BinOp::Add => {
                         if self.infer_llvm_type(left) == "string" {
                             self.gen_string_concat(&left_reg, &right_reg)
+                        } else if self.options.sanitize {
+                            self.gen_checked_arithmetic("sadd", "+", &left_reg, &right_reg)
                         } else {
                             let result = self.new_temp();
                             self.emit(&format!("  {} = add i64 {}, {}", result, left_reg, right_reg));
                             result
                         }
                     }
+                    BinOp::Sub if self.options.sanitize => {
+                        self.gen_checked_arithmetic("ssub", "-", &left_reg, &right_reg)
+                    }
                     BinOp::Sub => {
                         let result = self.new_temp();
                         self.emit(&format!("  {} = sub i64 {}, {}", result, left_reg, right_reg));
                         result
                     }
+                    BinOp::Mul if self.options.sanitize => {
+                        self.gen_checked_arithmetic("smul", "*", &left_reg, &right_reg)
+                    }
                     BinOp::Mul => {
                         let result = self.new_temp();
                         self.emit(&format!("  {} = mul i64 {}, {}", result, left_reg, right_reg));
@@ -497,71 +1178,81 @@ impl CodeGenerator {
                         result
                     }
                     BinOp::Mod => {
+                        // `%` is the truncated remainder (sign follows the dividend), so
+                        // -7 % 3 == -1. The `mod` builtin provides floored modulo instead.
                         let result = self.new_temp();
                         self.emit(&format!("  {} = srem i64 {}, {}", result, left_reg, right_reg));
                         result
                     }
+                    BinOp::Pow => {
+                        let result = self.new_temp();
+                        self.emit(&format!("  {} = call i64 @pow_impl(i64 {}, i64 {})", result, left_reg, right_reg));
+                        result
+                    }
                     BinOp::Equal => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp eq i64 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp eq {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
                     BinOp::NotEqual => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp ne i64 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp ne {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
                     BinOp::LessThan => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp slt i64 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp slt {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
                     BinOp::LessEqual => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp sle i64 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp sle {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
                     BinOp::GreaterThan => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp sgt i64 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp sgt {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
                     BinOp::GreaterEqual => {
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = icmp sge i64 {}, {}", result, left_reg, right_reg));
-                        result
-                    }
-                    BinOp::And => {
-                        let result = self.new_temp();
-                        self.emit(&format!("  {} = and i1 {}, {}", result, left_reg, right_reg));
-                        result
-                    }
-                    BinOp::Or => {
-                        let result = self.new_temp();
-                        self.emit(&format!("  {} = or i1 {}, {}", result, left_reg, right_reg));
+                        self.emit(&format!("  {} = icmp sge {} {}, {}", result, width, left_reg, right_reg));
                         result
                     }
+                    BinOp::And | BinOp::Or => unreachable!("handled by gen_short_circuit"),
                 }
             }
 
             AstNode::UnaryOp { op, operand } => {
                 let operand_reg = self.gen_node(operand);
-                let result = self.new_temp();
+                let operand_type = self.infer_llvm_type(operand);
 
                 match op {
-                    crate::parser::UnOp::Not => {
+                    UnOp::Not => {
+                        let operand_reg = self.coerce(&operand_reg, &operand_type, "bool");
+                        let result = self.new_temp();
                         self.emit(&format!("  {} = xor i1 {}, true", result, operand_reg));
+                        result
+                    }
+                    UnOp::Negate if operand_type == "float" => {
+                        let result = self.new_temp();
+                        self.emit(&format!("  {} = fneg double {}", result, operand_reg));
+                        result
                     }
-                    crate::parser::UnOp::Negate => {
+                    UnOp::Negate => {
+                        let operand_reg = self.coerce(&operand_reg, &operand_type, "int");
+                        let result = self.new_temp();
                         self.emit(&format!("  {} = sub i64 0, {}", result, operand_reg));
+                        result
                     }
                 }
-
-                result
             }
 
             AstNode::Number(n) => n.to_string(),
 
+            // Hex is the one spelling of a double LLVM accepts for every value
+            AstNode::Float(f) => format!("0x{:016X}", f.to_bits()),
+
             AstNode::Boolean(b) => {
                 if *b { "1" } else { "0" }.to_string()
             }
@@ -588,11 +1279,14 @@ impl CodeGenerator {
 
                 let size = elements.len();
                 let array_type = format!("[{} x i64]", size);
-                let ptr = self.new_temp();
+                let ptr = self.new_slot();
                 self.emit(&format!("  {} = alloca {}", ptr, array_type));
 
                 for (i, elem) in elements.iter().enumerate() {
                     let value = self.gen_node(elem);
+                    // Bools and chars are widened to fill an `i64` slot
+                    let elem_type = self.infer_llvm_type(elem);
+                    let value = self.coerce(&value, &elem_type, "int");
                     let elem_ptr = self.new_temp();
                     self.emit(&format!("  {} = getelementptr [{} x i64], [{} x i64]* {}, i64 0, i64 {}", 
                         elem_ptr, size, size, ptr, i));
@@ -605,9 +1299,39 @@ impl CodeGenerator {
             AstNode::Index { array, index } => {
                 let index_val = self.gen_node(index);
 
+                if self.infer_llvm_type(array) == "[char]" {
+                    let base = self.gen_node(array);
+                    self.gen_chars_bounds_check(&base, &index_val);
+                    let elem_ptr = self.new_temp();
+                    self.emit(&format!("  {} = getelementptr i8, i8* {}, i64 {}", elem_ptr, base, index_val));
+                    let result = self.new_temp();
+                    self.emit(&format!("  {} = load i8, i8* {}", result, elem_ptr));
+                    return result;
+                }
+
+                if let AstNode::Identifier { name, .. } = array.as_ref()
+                    && let Some(meta) = self.current_function_vars.get(name).cloned()
+                    && meta.var_type == "[string]"
+                {
+                    if self.options.sanitize {
+                        let len = self.gen_len(array);
+                        self.gen_bounds_check(&index_val, &len);
+                    }
+                    let base = self.new_temp();
+                    self.emit(&format!("  {} = load i8**, i8*** {}", base, meta.llvm_name));
+                    let elem_ptr = self.new_temp();
+                    self.emit(&format!("  {} = getelementptr i8*, i8** {}, i64 {}", elem_ptr, base, index_val));
+                    let result = self.new_temp();
+                    self.emit(&format!("  {} = load i8*, i8** {}", result, elem_ptr));
+                    return result;
+                }
+
                 let (array_ptr, array_size) = match array.as_ref() {
                     AstNode::Identifier { name, .. } => {
-                        if let Some(meta) = self.current_function_vars.get(name) {
+                        if let Some(meta) = self.current_function_vars.get(name).cloned() {
+                            if let Some(size) = meta.array_size {
+                                self.gen_bounds_check(&index_val, &size.to_string());
+                            }
                             let size = meta.array_size.unwrap_or(100);
                             (meta.llvm_name.clone(), size)
                         } else {
@@ -615,6 +1339,7 @@ impl CodeGenerator {
                             return "0".to_string();
                         }
                     }
+                    AstNode::ArrayLit(elements) => (self.gen_node(array), elements.len()),
                     _ => (self.gen_node(array), 100),
                 };
 
@@ -625,15 +1350,31 @@ impl CodeGenerator {
                     elem_ptr, array_size, array_size, array_ptr, index_val));
                 self.emit(&format!("  {} = load i64, i64* {}", result, elem_ptr));
 
-                result
+                match self.array_element_type(array) {
+                    Some(element_type) => self.coerce(&result, "int", &element_type),
+                    None => result,
+                }
             }
 
             AstNode::Identifier { name, .. } => {
                 if let Some(meta) = self.current_function_vars.get(name).cloned() {
+                    // Fixed-size arrays are used through their `[N x i64]*` directly
+                    if meta.array_size.is_some() {
+                        return meta.llvm_name;
+                    }
+                    if self.options.optimize
+                        && let Some(cached) = self.load_cache.get(&meta.llvm_name)
+                    {
+                        return cached.clone();
+                    }
+
                     let result = self.new_temp();
                     let llvm_type_str = self.type_to_llvm(&meta.var_type).to_string();
                     let llvm_name = meta.llvm_name.clone();
                     self.emit(&format!("  {} = load {}, {}* {}", result, llvm_type_str, llvm_type_str, llvm_name));
+                    if self.options.optimize {
+                        self.load_cache.insert(llvm_name, result.clone());
+                    }
                     result
                 } else {
                     eprintln!("CODEGEN ERROR: Variable '{}' not found in current scope!", name);
@@ -641,7 +1382,7 @@ impl CodeGenerator {
                 }
             }
 
-            AstNode::Reference(expr) => {
+            AstNode::Reference { value: expr, .. } => {
                 match expr.as_ref() {
                     AstNode::Identifier { name, .. } => {
                         if let Some(meta) = self.current_function_vars.get(name).cloned() {
@@ -660,7 +1401,7 @@ impl CodeGenerator {
                 }
             }
 
-            AstNode::Call { name, args } => {
+            AstNode::Call { name, args, .. } => {
                 match name.as_str() {
                     "puts" if !args.is_empty() => {
                         let arg_reg = self.gen_node(&args[0]);
@@ -681,6 +1422,11 @@ impl CodeGenerator {
                         let filename_reg = self.gen_node(&args[0]);
                         let result = self.new_temp();
                         self.emit(&format!("  {} = call i8* @read_file_impl(i8* {})", result, filename_reg));
+                        if self.options.sanitize {
+                            let failed = self.new_temp();
+                            self.emit(&format!("  {} = icmp eq i8* {}, null", failed, result));
+                            self.gen_runtime_check(&failed, "read_file could not read the file", "");
+                        }
                         result
                     }
                     "write_file" if args.len() >= 2 => {
@@ -692,13 +1438,73 @@ impl CodeGenerator {
                         self.emit(&format!("  {} = sext i32 {} to i64", result_i64, result));
                         result_i64
                     }
+                    "format" if !args.is_empty() => self.gen_format(args),
+                    "assert_eq" if args.len() >= 2 => self.gen_assert_eq(&args[0], &args[1]),
+                    "len" if !args.is_empty() => self.gen_len(&args[0]),
+                    // A `[char]` is a heap copy of the string bytes, so both directions are a copy
+                    "to_chars" | "from_chars" if !args.is_empty() => {
+                        let source = self.gen_node(&args[0]);
+                        self.gen_string_copy(&source)
+                    }
+                    "print" if !args.is_empty() => {
+                        self.gen_print_value(&args[0]);
+                        "0".to_string()
+                    }
+                    "println" if !args.is_empty() => {
+                        self.gen_print_value(&args[0]);
+                        self.gen_printf("\n", "")
+                    }
+                    "clamp" if args.len() >= 3 => {
+                        let clamp_type = self.infer_llvm_type(node);
+                        let mut bounds = Vec::new();
+                        for arg in &args[..3] {
+                            let reg = self.gen_node(arg);
+                            let arg_type = self.infer_llvm_type(arg);
+                            bounds.push(self.coerce(&reg, &arg_type, &clamp_type));
+                        }
+                        self.gen_clamp(&bounds[0], &bounds[1], &bounds[2], &clamp_type)
+                    }
+                    "lerp" if args.len() >= 3 => {
+                        let mut operands = Vec::new();
+                        for arg in &args[..3] {
+                            let reg = self.gen_node(arg);
+                            let arg_type = self.infer_llvm_type(arg);
+                            operands.push(self.coerce(&reg, &arg_type, "float"));
+                        }
+                        self.gen_lerp(&operands[0], &operands[1], &operands[2])
+                    }
+                    "min" | "max" if !args.is_empty() => self.gen_array_extreme(&args[0], name == "max"),
+                    "bench" if let [count, AstNode::StringLit(target)] = args.as_slice() => {
+                        let count_reg = self.gen_node(count);
+                        self.gen_bench(&count_reg, target)
+                    }
+                    "between" if args.len() >= 3 => {
+                        let value_type = self.infer_llvm_type(&args[0]);
+                        let llvm_type = self.type_to_llvm(&value_type).to_string();
+                        let value = self.gen_node(&args[0]);
+                        let lo = self.gen_node(&args[1]);
+                        let hi = self.gen_node(&args[2]);
+                        self.gen_between(&llvm_type, &value, &lo, &hi)
+                    }
+                    "mod" if args.len() >= 2 => {
+                        let left_reg = self.gen_node(&args[0]);
+                        let right_reg = self.gen_node(&args[1]);
+                        self.gen_floored_mod(&left_reg, &right_reg)
+                    }
                     _ => {
+                        let target = self.resolve_function(name);
+                        let params = self.function_params.get(&target).cloned().unwrap_or_default();
+                        let defaults: Vec<AstNode> = params.iter()
+                            .skip(args.len())
+                            .filter_map(|param| param.default_value.clone())
+                            .collect();
+
                         let mut arg_regs = Vec::new();
                         let mut arg_types = Vec::new();
 
-                        for arg_node in args {
+                        for (i, arg_node) in args.iter().chain(&defaults).enumerate() {
                             match arg_node {
-                                AstNode::Reference(inner) => {
+                                AstNode::Reference { value: inner, .. } => {
                                     match inner.as_ref() {
                                         AstNode::Identifier { name: var_name, .. } => {
                                             if let Some(meta) = self.current_function_vars.get(var_name) {
@@ -723,8 +1529,18 @@ impl CodeGenerator {
                                 }
                                 _ => {
                                     let reg = self.gen_node(arg_node);
-                                    let arg_type = self.infer_llvm_type(arg_node);
-                                    arg_regs.push(reg);
+                                    let mut arg_type = self.infer_llvm_type(arg_node);
+                                    // A scalar is passed at the width the callee declared, e.g. a
+                                    // comparison result as `i1` or a char as `i64`
+                                    if let Some(param) = params.get(i)
+                                        && is_scalar(&param.param_type)
+                                        && is_scalar(&arg_type)
+                                    {
+                                        arg_regs.push(self.coerce(&reg, &arg_type, &param.param_type));
+                                        arg_type = param.param_type.clone();
+                                    } else {
+                                        arg_regs.push(reg);
+                                    }
                                     arg_types.push(self.type_to_llvm(&arg_type).to_string());
                                 }
                             }
@@ -736,12 +1552,17 @@ impl CodeGenerator {
                             .collect::<Vec<_>>()
                             .join(", ");
 
-                        let return_type = self.function_signatures.get(name)
+                        let return_type = self.function_signatures.get(&target)
                             .cloned()
                             .unwrap_or_else(|| "i64".to_string());
 
+                        if return_type == "void" {
+                            self.emit(&format!("  call void @{}({})", target, args_str));
+                            return "0".to_string();
+                        }
+
                         let result = self.new_temp();
-                        self.emit(&format!("  {} = call {} @{}({})", result, return_type, name, args_str));
+                        self.emit(&format!("  {} = call {} @{}({})", result, return_type, target, args_str));
                         result
                     }
                 }
@@ -749,12 +1570,8 @@ impl CodeGenerator {
 
             AstNode::MethodCall { object, method, args } => {
                 match method.as_str() {
-                    "len" => {
-                        let obj_reg = self.gen_node(object);
-                        let result = self.new_temp();
-                        self.emit(&format!("  {} = call i64 @strlen(i8* {})", result, obj_reg));
-                        result
-                    }
+                    // Element count for arrays; byte length (`strlen`) for strings
+                    "len" => self.gen_len(object),
                     "char_at" if !args.is_empty() => {
                         let obj_reg = self.gen_node(object);
                         let index_reg = self.gen_node(&args[0]);
@@ -766,6 +1583,9 @@ impl CodeGenerator {
                         self.emit(&format!("  {} = sext i8 {} to i64", extended, result));
                         extended
                     }
+                    "contains" | "index_of" if args.len() == 1 => {
+                        self.gen_array_search(object, &args[0], method == "index_of")
+                    }
                     "push" if !args.is_empty() => {
                         "0".to_string()
                     }
@@ -779,21 +1599,22 @@ impl CodeGenerator {
 
     fn gen_function(&mut self, name: &str, params: &[Parameter], body: &AstNode, return_type: &Option<String>) -> String {
         self.current_function_vars.clear();
+        self.moved_slots.clear();
+        // Temps and labels are function-local in LLVM, so numbering restarts per function
+        // and a function's IR does not depend on what was generated before it
         self.temp_counter = 0;
+        self.slot_counter = 0;
+        self.label_counter = 0;
 
-        let ret_type = if name == "main" {
-            "i32".to_string()
-        } else if let Some(rt) = return_type {
-            self.type_to_llvm(rt).to_string()
-        } else {
-            "void".to_string()
-        };
-
-        self.function_signatures.insert(name.to_string(), ret_type.clone());
+        let symbol = self.symbol_name(name);
+        let is_entry = symbol == "main";
+        let ret_type = self.register_signature(&symbol, &effective_return_type(return_type, params, body));
         self.current_function_name = name.to_string();
         self.current_function_return_type = ret_type.clone();
 
-        let param_list = if params.is_empty() {
+        let param_list = if is_entry && params.len() == 1 {
+            "i32 %argc, i8** %argv".to_string()
+        } else if params.is_empty() {
             String::new()
         } else {
             params.iter()
@@ -819,10 +1640,15 @@ impl CodeGenerator {
                 .join(", ")
         };
 
-        self.emit(&format!("\ndefine {} @{}({}) {{", ret_type, name, param_list));
+        self.emit(&format!("\ndefine {} @{}({}) {{", ret_type, symbol, param_list));
         self.emit("entry:");
+        let body_start = self.output.len();
+
+        if is_entry && params.len() == 1 {
+            self.gen_main_args(&params[0].name);
+        }
 
-        for param in params {
+        for param in params.iter().filter(|_| !is_entry) {
             if param.is_reference {
                 let param_type_name = param.param_type.clone();
 
@@ -842,12 +1668,13 @@ impl CodeGenerator {
                     is_heap: false,
                     array_size,
                     is_string_literal: false,
+                    element_type: None,
                 });
             } else {
                 let param_type_str = self.type_to_llvm(&param.param_type).to_string();
                 let param_type_name = param.param_type.clone();
 
-                let ptr = self.new_temp();
+                let ptr = self.new_slot();
                 self.emit(&format!("  {} = alloca {}", ptr, param_type_str));
                 self.emit(&format!("  store {} %arg_{}, {}* {}", param_type_str, param.name, param_type_str, ptr));
 
@@ -857,22 +1684,323 @@ impl CodeGenerator {
                     is_heap: false,
                     array_size: None,
                     is_string_literal: false,
+                    element_type: None,
                 });
             }
         }
 
-        self.gen_node(body);
+        // `return f(...)` inside `f` becomes a jump back here, so tail recursion
+        // runs in constant stack space
+        self.tail_call = None;
+        if !is_entry && !params.is_empty() && !params.iter().any(|p| p.is_reference)
+            && has_self_tail_call(body, name.rsplit('.').next().unwrap_or(name))
+        {
+            let label = self.new_label("tail_entry");
+            let slots = params
+                .iter()
+                .map(|p| {
+                    let slot = self.current_function_vars[&p.name].llvm_name.clone();
+                    (slot, self.type_to_llvm(&p.param_type).to_string())
+                })
+                .collect();
+            self.emit(&format!("  br label %{}", label));
+            self.emit(&format!("{}:", label));
+            self.tail_call = Some(TailCallTarget { symbol: symbol.clone(), params: slots, label });
+        }
 
-        if name == "main" {
-            self.emit("  ret i32 0");
-        } else if return_type.is_none() {
-            self.emit("  ret void");
+        self.block_terminated = false;
+        self.gen_node(body);
+        self.tail_call = None;
+
+        // Falling off the end of the body returns a zero value of the function's type
+        if !self.block_terminated {
+            if is_entry {
+                self.emit("  ret i32 0");
+            } else if ret_type == "void" {
+                self.emit("  ret void");
+            } else {
+                self.emit(&format!("  ret {} zeroinitializer", ret_type));
+            }
         }
 
+        self.hoist_allocas(body_start);
         self.emit("}");
         String::new()
     }
 
+    /// Moves every stack slot of the function that starts at `body_start` into its
+    /// entry block. A slot allocated inside a loop (or after a tail call's jump
+    /// target) would otherwise grow the stack on every iteration. Slots use named
+    /// values, so moving them does not disturb the `%N` temp numbering.
+    fn hoist_allocas(&mut self, body_start: usize) {
+        let body = self.output.split_off(body_start);
+        let (allocas, rest): (Vec<&str>, Vec<&str>) = body
+            .lines()
+            .partition(|line| line.starts_with("  %slot") && line.contains(" = alloca "));
+        for line in allocas.into_iter().chain(rest) {
+            self.output.push_str(line);
+            self.output.push('\n');
+        }
+    }
+
+    /// `for i in start..end`: both bounds are evaluated once, then `i` counts up
+    /// from `start` while it is below `end`.
+    fn gen_range_for(&mut self, variable: &str, start: &AstNode, end: &AstNode, body: &AstNode) {
+        let start_label = self.new_label("for_start");
+        let body_label = self.new_label("for_body");
+        let step_label = self.new_label("for_step");
+        let end_label = self.new_label("for_end");
+
+        let first = self.gen_node(start);
+        let bound = self.gen_node(end);
+        let counter = self.new_slot();
+        self.emit(&format!("  {} = alloca i64", counter));
+        self.emit(&format!("  store i64 {}, i64* {}", first, counter));
+
+        // The body gets its own copy so assigning to it cannot derail the count
+        let loop_var = self.new_slot();
+        self.emit(&format!("  {} = alloca i64", loop_var));
+        let vars_before = self.current_function_vars.clone();
+        self.current_function_vars.insert(variable.to_string(), VarMetadata {
+            llvm_name: loop_var.clone(),
+            var_type: "int".to_string(),
+            is_heap: false,
+            array_size: None,
+            is_string_literal: false,
+            element_type: None,
+        });
+
+        self.loop_stack.push(LoopLabels {
+            continue_label: step_label.clone(),
+            break_label: end_label.clone(),
+        });
+        self.emit(&format!("  br label %{}", start_label));
+
+        self.emit(&format!("{}:", start_label));
+        let current = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* {}", current, counter));
+        let cond = self.new_temp();
+        self.emit(&format!("  {} = icmp slt i64 {}, {}", cond, current, bound));
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", cond, body_label, end_label));
+
+        self.emit(&format!("{}:", body_label));
+        self.emit(&format!("  store i64 {}, i64* {}", current, loop_var));
+        self.block_terminated = false;
+        self.gen_node(body);
+        if !self.block_terminated {
+            self.emit(&format!("  br label %{}", step_label));
+        }
+
+        self.emit(&format!("{}:", step_label));
+        let next = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, 1", next, current));
+        self.emit(&format!("  store i64 {}, i64* {}", next, counter));
+        self.emit(&format!("  br label %{}", start_label));
+
+        self.emit(&format!("{}:", end_label));
+        self.block_terminated = false;
+        self.loop_stack.pop();
+        self.current_function_vars = vars_before;
+    }
+
+    /// Frees every heap value owned by a variable of the current function, except
+    /// those named in `keep`, ahead of a `return` or a tail call.
+    fn gen_free_heap_vars(&mut self, keep: &[&str]) {
+        let mut owned: Vec<(String, VarMetadata)> = self.current_function_vars
+            .iter()
+            .filter(|(name, meta)| {
+                meta.is_heap
+                    && !meta.is_string_literal
+                    && !self.moved_slots.contains(&meta.llvm_name)
+                    && !keep.contains(&name.as_str())
+            })
+            .map(|(name, meta)| (name.clone(), meta.clone()))
+            .collect();
+        // Map order is random; keep the IR stable
+        owned.sort_by(|(a, _), (b, _)| a.cmp(b));
+        let vars: Vec<VarMetadata> = owned.into_iter().map(|(_, meta)| meta).collect();
+        self.gen_frees(&vars);
+    }
+
+    fn gen_frees(&mut self, vars: &[VarMetadata]) {
+        for meta in vars {
+            let llvm_type = self.type_to_llvm(&meta.var_type).to_string();
+            let ptr_reg = self.new_temp();
+            self.emit(&format!("  {} = load {}, {}* {}", ptr_reg, llvm_type, llvm_type, meta.llvm_name));
+            let ptr_reg = if llvm_type == "i8*" {
+                ptr_reg
+            } else {
+                let raw = self.new_temp();
+                self.emit(&format!("  {} = bitcast {} {} to i8*", raw, llvm_type, ptr_reg));
+                raw
+            };
+            self.emit(&format!("  call void @free(i8* {})", ptr_reg));
+        }
+    }
+
+    fn is_enum_type(&self, type_name: &str) -> bool {
+        type_name == "enum" || self.enum_types.contains_key(type_name)
+    }
+
+    /// Makes a returned enum value one the caller can own and free. Fresh values and
+    /// the function's own variables are handed over as they are; anything else, such
+    /// as a parameter or a struct field, is still owned elsewhere and is copied.
+    fn gen_returned_enum(&mut self, value: &AstNode, value_reg: &str) -> String {
+        let owned = match value {
+            AstNode::EnumValue { .. } | AstNode::Call { .. } => true,
+            AstNode::Identifier { name, .. } => self.current_function_vars.get(name).is_some_and(|meta| meta.is_heap),
+            _ => false,
+        };
+        if owned || !self.is_enum_type(&self.infer_llvm_type(value)) {
+            return value_reg.to_string();
+        }
+
+        let raw = self.new_temp();
+        self.emit(&format!("  {} = call i8* @malloc(i64 16)", raw));
+        let copy = self.new_temp();
+        self.emit(&format!("  {} = bitcast i8* {} to {{ i32, i64 }}*", copy, raw));
+        let contents = self.new_temp();
+        self.emit(&format!("  {} = load {{ i32, i64 }}, {{ i32, i64 }}* {}", contents, value_reg));
+        self.emit(&format!("  store {{ i32, i64 }} {}, {{ i32, i64 }}* {}", contents, copy));
+        copy
+    }
+
+    /// Evaluates every argument, then overwrites the parameters and jumps back to
+    /// the start of the function body. Heap values owned by locals are freed first,
+    /// as for a `return`, except those passed on as arguments.
+    fn gen_tail_call(&mut self, args: &[AstNode], target: &TailCallTarget) {
+        let arg_regs: Vec<String> = args.iter().map(|arg| self.gen_node(arg)).collect();
+        let passed: Vec<&str> = args
+            .iter()
+            .filter_map(|arg| match arg {
+                AstNode::Identifier { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        self.gen_free_heap_vars(&passed);
+        for ((slot, llvm_type), reg) in target.params.iter().zip(&arg_regs) {
+            self.emit(&format!("  store {} {}, {}* {}", llvm_type, reg, llvm_type, slot));
+        }
+        self.emit(&format!("  br label %{}", target.label));
+        self.block_terminated = true;
+    }
+
+    /// Records a function's LLVM and source return types so calls can be typed.
+    fn register_signature(&mut self, name: &str, return_type: &Option<String>) -> String {
+        let ret_type = if name == "main" {
+            "i32".to_string()
+        } else if let Some(rt) = return_type {
+            self.type_to_llvm(rt).to_string()
+        } else {
+            "void".to_string()
+        };
+
+        self.function_signatures.insert(name.to_string(), ret_type.clone());
+        if let Some(rt) = return_type {
+            self.function_return_types.insert(name.to_string(), rt.clone());
+        }
+        ret_type
+    }
+
+    /// Maps a called name to its symbol, preferring the innermost visible nested function.
+    fn resolve_function(&self, name: &str) -> String {
+        self.function_aliases
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).cloned())
+            .unwrap_or_else(|| self.symbol_name(name))
+    }
+
+    fn entry_name(&self) -> &str {
+        self.options.entry.as_deref().unwrap_or("main")
+    }
+
+    /// The entry function is always emitted as `@main`; a user function that happens
+    /// to be called `main` while another entry is configured is renamed out of the way.
+    fn symbol_name(&self, name: &str) -> String {
+        if name == self.entry_name() {
+            "main".to_string()
+        } else if name == "main" {
+            "main.user".to_string()
+        } else {
+            name.to_string()
+        }
+    }
+
+    /// Binds `main`'s `args: [string]` parameter to `argv` without the program name.
+    /// Its length is kept in the hidden variable `<name>.len`.
+    fn gen_main_args(&mut self, name: &str) {
+        let rest = self.new_temp();
+        self.emit(&format!("  {} = getelementptr i8*, i8** %argv, i64 1", rest));
+        let args_ptr = self.new_slot();
+        self.emit(&format!("  {} = alloca i8**", args_ptr));
+        self.emit(&format!("  store i8** {}, i8*** {}", rest, args_ptr));
+
+        let count = self.new_temp();
+        self.emit(&format!("  {} = sub i32 %argc, 1", count));
+        let count_wide = self.new_temp();
+        self.emit(&format!("  {} = sext i32 {} to i64", count_wide, count));
+        let len_ptr = self.new_slot();
+        self.emit(&format!("  {} = alloca i64", len_ptr));
+        self.emit(&format!("  store i64 {}, i64* {}", count_wide, len_ptr));
+
+        self.current_function_vars.insert(name.to_string(), VarMetadata {
+            llvm_name: args_ptr,
+            var_type: "[string]".to_string(),
+            is_heap: false,
+            array_size: None,
+            is_string_literal: false,
+            element_type: None,
+        });
+        self.current_function_vars.insert(format!("{}.len", name), VarMetadata {
+            llvm_name: len_ptr,
+            var_type: "int".to_string(),
+            is_heap: false,
+            array_size: None,
+            is_string_literal: false,
+            element_type: None,
+        });
+    }
+
+    fn gen_len(&mut self, arg: &AstNode) -> String {
+        if let AstNode::Identifier { name, .. } = arg
+            && let Some(meta) = self.current_function_vars.get(name).cloned()
+        {
+            if let Some(size) = meta.array_size {
+                return size.to_string();
+            }
+            if let Some(len_meta) = self.current_function_vars.get(&format!("{}.len", name)).cloned() {
+                let result = self.new_temp();
+                self.emit(&format!("  {} = load i64, i64* {}", result, len_meta.llvm_name));
+                return result;
+            }
+        }
+
+        match arg {
+            AstNode::ArrayLit(elements) => return elements.len().to_string(),
+            // Agrees with `strlen`, which stops at an embedded `\0`
+            AstNode::StringLit(literal) => return literal.find('\0').unwrap_or(literal.len()).to_string(),
+            _ => {}
+        }
+
+        let value = self.gen_node(arg);
+        let result = self.new_temp();
+        self.emit(&format!("  {} = call i64 @strlen(i8* {})", result, value));
+        result
+    }
+
+    fn gen_string_copy(&mut self, source: &str) -> String {
+        let len = self.new_temp();
+        self.emit(&format!("  {} = call i64 @strlen(i8* {})", len, source));
+        let size = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, 1", size, len));
+        let copy = self.new_temp();
+        self.emit(&format!("  {} = call i8* @malloc(i64 {})", copy, size));
+        let ignored = self.new_temp();
+        self.emit(&format!("  {} = call i8* @strcpy(i8* {}, i8* {})", ignored, copy, source));
+        copy
+    }
+
     fn gen_string_concat(&mut self, left: &str, right: &str) -> String {
         let len1 = self.new_temp();
         let len2 = self.new_temp();
@@ -899,43 +2027,1099 @@ impl CodeGenerator {
         new_ptr
     }
 
+    /// Enum values are equal when both their tags and payloads match. Variants
+    /// without a payload always store 0, so comparing payloads is safe for them too.
+    fn gen_enum_eq(&mut self, left: &str, right: &str, negate: bool) -> String {
+        let mut fields = Vec::new();
+        for (index, field_type) in [(0, "i32"), (1, "i64")] {
+            let mut loaded = Vec::new();
+            for value in [left, right] {
+                let field_ptr = self.new_temp();
+                self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 {}", field_ptr, value, index));
+                let field = self.new_temp();
+                self.emit(&format!("  {} = load {}, {}* {}", field, field_type, field_type, field_ptr));
+                loaded.push(field);
+            }
+            let same = self.new_temp();
+            self.emit(&format!("  {} = icmp eq {} {}, {}", same, field_type, loaded[0], loaded[1]));
+            fields.push(same);
+        }
+
+        let equal = self.new_temp();
+        self.emit(&format!("  {} = and i1 {}, {}", equal, fields[0], fields[1]));
+        if !negate {
+            return equal;
+        }
+
+        let result = self.new_temp();
+        self.emit(&format!("  {} = xor i1 {}, true", result, equal));
+        result
+    }
+
+    /// The elements of a fixed-size array as nodes: the literal's own elements,
+    /// or an `Index` into the variable for each position.
+    fn array_elements(&self, arg: &AstNode) -> Option<Vec<AstNode>> {
+        match arg {
+            AstNode::ArrayLit(elements) => Some(elements.clone()),
+            AstNode::Identifier { name, .. } => {
+                let len = self.current_function_vars.get(name)?.array_size?;
+                Some((0..len)
+                    .map(|i| AstNode::Index {
+                        array: Box::new(arg.clone()),
+                        index: Box::new(AstNode::Number(i as i64)),
+                    })
+                    .collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// The bool or char element type of a fixed-size array literal or variable.
+    fn array_element_type(&self, array: &AstNode) -> Option<String> {
+        match array {
+            AstNode::ArrayLit(elements) => {
+                let element_type = self.infer_llvm_type(elements.first()?);
+                matches!(element_type.as_str(), "bool" | "char").then_some(element_type)
+            }
+            AstNode::Identifier { name, .. } => self.current_function_vars.get(name)?.element_type.clone(),
+            _ => None,
+        }
+    }
+
+    /// `min`/`max` of an array: a chain of compare-and-select over its elements.
+    fn gen_array_extreme(&mut self, arg: &AstNode, want_max: bool) -> String {
+        let elements = self.array_elements(arg).unwrap_or_default();
+        let Some((first, rest)) = elements.split_first() else {
+            return "0".to_string();
+        };
+
+        let predicate = if want_max { "sgt" } else { "slt" };
+        let mut best = self.gen_node(first);
+        for element in rest {
+            let value = self.gen_node(element);
+            let better = self.new_temp();
+            self.emit(&format!("  {} = icmp {} i64 {}, {}", better, predicate, value, best));
+            let selected = self.new_temp();
+            self.emit(&format!("  {} = select i1 {}, i64 {}, i64 {}", selected, better, value, best));
+            best = selected;
+        }
+        best
+    }
+
+    /// `contains` (an `or` of element comparisons) or `index_of` (a select chain
+    /// built from the last element back, so the first match wins; -1 if none).
+    fn gen_array_search(&mut self, array: &AstNode, needle: &AstNode, want_index: bool) -> String {
+        let elements = self.array_elements(array).unwrap_or_default();
+        let needle_reg = self.gen_node(needle);
+
+        let mut matches = Vec::new();
+        for element in &elements {
+            let value = self.gen_node(element);
+            let equal = self.new_temp();
+            self.emit(&format!("  {} = icmp eq i64 {}, {}", equal, value, needle_reg));
+            matches.push(equal);
+        }
+
+        if want_index {
+            let mut result = "-1".to_string();
+            for (i, equal) in matches.iter().enumerate().rev() {
+                let selected = self.new_temp();
+                self.emit(&format!("  {} = select i1 {}, i64 {}, i64 {}", selected, equal, i, result));
+                result = selected;
+            }
+            result
+        } else {
+            let mut result = "false".to_string();
+            for equal in matches {
+                let combined = self.new_temp();
+                self.emit(&format!("  {} = or i1 {}, {}", combined, result, equal));
+                result = combined;
+            }
+            result
+        }
+    }
+
+    /// Binds the payload of the enum value at `value_reg` to a new variable.
+    fn bind_enum_payload(&mut self, value_reg: &str, pattern: &Pattern, binding: &str) {
+        let payload_type = match pattern {
+            Pattern::EnumPattern { enum_name, variant, .. } => self.payload_type(enum_name, variant),
+            _ => "int".to_string(),
+        };
+        let val_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 1", val_ptr, value_reg));
+        let raw = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* {}", raw, val_ptr));
+        let val = self.unpack_payload(&raw, &payload_type);
+
+        let llvm_type = self.type_to_llvm(&payload_type).to_string();
+        let var_ptr = self.new_slot();
+        self.emit(&format!("  {} = alloca {}", var_ptr, llvm_type));
+        self.emit(&format!("  store {} {}, {}* {}", llvm_type, val, llvm_type, var_ptr));
+
+        self.current_function_vars.insert(binding.to_string(), VarMetadata {
+            llvm_name: var_ptr,
+            var_type: payload_type,
+            is_heap: false,
+            array_size: None,
+            is_string_literal: false,
+            element_type: None,
+        });
+    }
+
+    /// Declared payload type of `enum_name::variant`; `int` when unknown.
+    fn payload_type(&self, enum_name: &str, variant: &str) -> String {
+        self.enum_types
+            .get(enum_name)
+            .and_then(|variants| variants.iter().find(|v| v.name == variant))
+            .and_then(|v| v.value_type.clone())
+            .unwrap_or_else(|| "int".to_string())
+    }
+
+    /// Packs a payload of source type `value_type` into the `i64` slot of an enum value.
+    fn pack_payload(&mut self, reg: &str, value_type: &str) -> String {
+        let llvm_type = self.type_to_llvm(value_type).to_string();
+        let instruction = match value_type {
+            "bool" | "char" => return self.coerce(reg, value_type, "int"),
+            "float" => format!("bitcast double {} to i64", reg),
+            _ if llvm_type.ends_with('*') => format!("ptrtoint {} {} to i64", llvm_type, reg),
+            _ => return reg.to_string(),
+        };
+        let result = self.new_temp();
+        self.emit(&format!("  {} = {}", result, instruction));
+        result
+    }
+
+    /// Unpacks an enum payload read as `i64` back into its source type.
+    fn unpack_payload(&mut self, raw: &str, payload_type: &str) -> String {
+        let llvm_type = self.type_to_llvm(payload_type).to_string();
+        let instruction = match payload_type {
+            "bool" | "char" => return self.coerce(raw, "int", payload_type),
+            "float" => format!("bitcast i64 {} to double", raw),
+            _ if llvm_type.ends_with('*') => format!("inttoptr i64 {} to {}", raw, llvm_type),
+            _ => return raw.to_string(),
+        };
+        let result = self.new_temp();
+        self.emit(&format!("  {} = {}", result, instruction));
+        result
+    }
+
+    /// Prints a value in its default display form without a trailing newline:
+    /// arrays as `[1, 2, 3]` (chars quoted, as in `['a', 'b']`), enums as
+    /// `Color::Red` or `Option::Some(5)`, structs as `Pt { x: 1, y: 2 }`.
+    fn gen_print_value(&mut self, arg: &AstNode) {
+        let value_type = self.infer_llvm_type(arg);
+
+        if let Some(elements) = self.array_elements(arg) {
+            self.gen_printf("[", "");
+            for (i, element) in elements.iter().enumerate() {
+                if i > 0 {
+                    self.gen_printf(", ", "");
+                }
+                let quoted = self.infer_llvm_type(element) == "char";
+                if quoted {
+                    self.gen_printf("'", "");
+                }
+                self.gen_print_value(element);
+                if quoted {
+                    self.gen_printf("'", "");
+                }
+            }
+            self.gen_printf("]", "");
+            return;
+        }
+
+        let reg = self.gen_node(arg);
+        self.gen_print_reg(&reg, &value_type);
+    }
+
+    /// Prints an already generated value of source type `value_type`.
+    fn gen_print_reg(&mut self, reg: &str, value_type: &str) {
+        if value_type == "[char]" {
+            self.gen_print_chars(reg);
+            return;
+        }
+
+        if let Some(variants) = self.enum_types.get(value_type).cloned() {
+            self.gen_print_enum(value_type, &variants, reg);
+            return;
+        }
+
+        if let Some(fields) = self.struct_types.get(value_type).cloned() {
+            self.gen_print_struct(value_type, &fields, reg);
+            return;
+        }
+
+        match value_type {
+            "string" => {
+                self.gen_printf("%s", &format!(", i8* {}", reg));
+            }
+            "bool" => {
+                let text = self.gen_bool_to_string(reg);
+                self.gen_printf("%s", &format!(", i8* {}", text));
+            }
+            "char" => {
+                let widened = self.new_temp();
+                self.emit(&format!("  {} = sext i8 {} to i32", widened, reg));
+                self.gen_printf("%c", &format!(", i32 {}", widened));
+            }
+            "float" => {
+                let shown = self.gen_float_for_display(reg);
+                self.gen_printf(FLOAT_FORMAT, &format!(", double {}", shown));
+            }
+            _ => {
+                self.gen_printf("%lld", &format!(", i64 {}", reg));
+            }
+        }
+    }
+
+    /// Prints a struct as `Pt { x: 1, y: 2 }`, with char fields quoted.
+    fn gen_print_struct(&mut self, struct_name: &str, fields: &[Field], value_reg: &str) {
+        self.gen_printf(&format!("{} {{ ", struct_name), "");
+        for (i, field) in fields.iter().enumerate() {
+            let separator = if i > 0 { ", " } else { "" };
+            self.gen_printf(&format!("{}{}: ", separator, field.name), "");
+            let field_ptr = self.new_temp();
+            self.emit(&format!(
+                "  {} = getelementptr %struct.{}, %struct.{}* {}, i32 0, i32 {}",
+                field_ptr, struct_name, struct_name, value_reg, i
+            ));
+            let llvm_type = self.type_to_llvm(&field.field_type).to_string();
+            let field_reg = self.new_temp();
+            self.emit(&format!("  {} = load {}, {}* {}", field_reg, llvm_type, llvm_type, field_ptr));
+            let quoted = field.field_type == "char";
+            if quoted {
+                self.gen_printf("'", "");
+            }
+            self.gen_print_reg(&field_reg, &field.field_type);
+            if quoted {
+                self.gen_printf("'", "");
+            }
+        }
+        self.gen_printf(" }", "");
+    }
+
+    /// Prints a `[char]` as `['h', 'i']`; its length is only known at runtime.
+    fn gen_print_chars(&mut self, chars: &str) {
+        let cond_label = self.new_label("print_chars_cond");
+        let body_label = self.new_label("print_chars_body");
+        let end_label = self.new_label("print_chars_end");
+
+        let len = self.new_temp();
+        self.emit(&format!("  {} = call i64 @strlen(i8* {})", len, chars));
+        let first = self.gen_string_ptr("'%c'");
+        let rest = self.gen_string_ptr(", '%c'");
+        self.gen_printf("[", "");
+        let entry_block = self.current_block.clone();
+        self.emit(&format!("  br label %{}", cond_label));
+
+        self.emit(&format!("{}:", cond_label));
+        let index = self.new_temp();
+        // Named rather than numbered: it is used by the phi before it is defined
+        let next = format!("%{}.next", body_label);
+        self.emit(&format!("  {} = phi i64 [ 0, %{} ], [ {}, %{} ]", index, entry_block, next, body_label));
+        let done = self.new_temp();
+        self.emit(&format!("  {} = icmp uge i64 {}, {}", done, index, len));
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", done, end_label, body_label));
+
+        self.emit(&format!("{}:", body_label));
+        let is_first = self.new_temp();
+        self.emit(&format!("  {} = icmp eq i64 {}, 0", is_first, index));
+        let format = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, i8* {}, i8* {}", format, is_first, first, rest));
+        let char_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr i8, i8* {}, i64 {}", char_ptr, chars, index));
+        let byte = self.new_temp();
+        self.emit(&format!("  {} = load i8, i8* {}", byte, char_ptr));
+        let widened = self.new_temp();
+        self.emit(&format!("  {} = sext i8 {} to i32", widened, byte));
+        let printed = self.new_temp();
+        self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {}, i32 {})", printed, format, widened));
+        self.emit(&format!("  {} = add i64 {}, 1", next, index));
+        self.emit(&format!("  br label %{}", cond_label));
+
+        self.emit(&format!("{}:", end_label));
+        self.gen_printf("]", "");
+    }
+
+    fn gen_print_enum(&mut self, enum_name: &str, variants: &[EnumVariant], value_reg: &str) {
+        let tag_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 0", tag_ptr, value_reg));
+        let tag = self.new_temp();
+        self.emit(&format!("  {} = load i32, i32* {}", tag, tag_ptr));
+        let payload_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr {{ i32, i64 }}, {{ i32, i64 }}* {}, i32 0, i32 1", payload_ptr, value_reg));
+        let payload = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* {}", payload, payload_ptr));
+
+        let end_label = self.new_label("print_enum_end");
+        let labels: Vec<String> = variants.iter().map(|_| self.new_label("print_variant")).collect();
+
+        let cases: String = labels
+            .iter()
+            .enumerate()
+            .map(|(i, label)| format!(" i32 {}, label %{}", i, label))
+            .collect();
+        self.emit(&format!("  switch i32 {}, label %{} [{} ]", tag, end_label, cases));
+
+        for (variant, label) in variants.iter().zip(&labels) {
+            self.emit(&format!("{}:", label));
+            if let Some(payload_type) = &variant.value_type {
+                let quote = if payload_type == "char" { "'" } else { "" };
+                self.gen_printf(&format!("{}::{}({}", enum_name, variant.name, quote), "");
+                let value = self.unpack_payload(&payload, payload_type);
+                self.gen_print_reg(&value, payload_type);
+                self.gen_printf(&format!("{})", quote), "");
+            } else {
+                self.gen_printf(&format!("{}::{}", enum_name, variant.name), "");
+            }
+            self.emit(&format!("  br label %{}", end_label));
+        }
+
+        self.emit(&format!("{}:", end_label));
+    }
+
+    /// Emits `printf(format, ...)`; `args` is either empty or starts with ", ".
+    fn gen_printf(&mut self, format: &str, args: &str) -> String {
+        let fmt_ptr = self.gen_string_ptr(format);
+        let result = self.new_temp();
+        self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {}{})", result, fmt_ptr, args));
+        result
+    }
+
+    fn gen_assert_eq(&mut self, left: &AstNode, right: &AstNode) -> String {
+        let value_type = self.infer_llvm_type(left);
+        let left_reg = self.gen_node(left);
+        let right_reg = self.gen_node(right);
+
+        let equal = if value_type == "string" {
+            let cmp = self.new_temp();
+            self.emit(&format!("  {} = call i32 @strcmp(i8* {}, i8* {})", cmp, left_reg, right_reg));
+            let equal = self.new_temp();
+            self.emit(&format!("  {} = icmp eq i32 {}, 0", equal, cmp));
+            equal
+        } else {
+            let llvm_type = self.type_to_llvm(&value_type).to_string();
+            let compare = if value_type == "float" { "fcmp oeq" } else { "icmp eq" };
+            let equal = self.new_temp();
+            self.emit(&format!("  {} = {} {} {}, {}", equal, compare, llvm_type, left_reg, right_reg));
+            equal
+        };
+
+        let fail_label = self.new_label("assert_fail");
+        let ok_label = self.new_label("assert_ok");
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", equal, ok_label, fail_label));
+
+        self.emit(&format!("{}:", fail_label));
+        let (conversion, args) = match value_type.as_str() {
+            "string" => ("%s", format!("i8* {}, i8* {}", left_reg, right_reg)),
+            "float" => {
+                let left_shown = self.gen_float_for_display(&left_reg);
+                let right_shown = self.gen_float_for_display(&right_reg);
+                (FLOAT_FORMAT, format!("double {}, double {}", left_shown, right_shown))
+            }
+            "bool" | "char" => {
+                let llvm_type = self.type_to_llvm(&value_type).to_string();
+                let extend = if value_type == "bool" { "zext" } else { "sext" };
+                let left_wide = self.new_temp();
+                self.emit(&format!("  {} = {} {} {} to i64", left_wide, extend, llvm_type, left_reg));
+                let right_wide = self.new_temp();
+                self.emit(&format!("  {} = {} {} {} to i64", right_wide, extend, llvm_type, right_reg));
+                ("%lld", format!("i64 {}, i64 {}", left_wide, right_wide))
+            }
+            _ => ("%lld", format!("i64 {}, i64 {}", left_reg, right_reg)),
+        };
+        let message = format!(
+            "assertion failed: `left == right`\n  left: `{}`\n right: `{}`\n",
+            conversion, conversion
+        );
+        let fmt_ptr = self.gen_string_ptr(&message);
+        let printed = self.new_temp();
+        self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {}, {})", printed, fmt_ptr, args));
+        self.emit("  call void @assert_fail_impl()");
+        self.emit("  unreachable");
+
+        self.emit(&format!("{}:", ok_label));
+        "0".to_string()
+    }
+
+    fn gen_test_harness(&mut self, nodes: &[AstNode]) {
+        let tests: Vec<String> = nodes
+            .iter()
+            .filter_map(|node| match node {
+                AstNode::FunctionDef { name, params, .. } if name.starts_with("test_") && params.is_empty() => {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        self.temp_counter = 0;
+        self.label_counter = 0;
+        self.emit("\ndefine i32 @main() {");
+        self.emit("entry:");
+        self.emit("  store i1 1, i1* @.test_mode");
+
+        for test in &tests {
+            let run_label = self.new_label("test_run");
+            let failed_label = self.new_label("test_failed");
+            let next_label = self.new_label("test_next");
+
+            let jump = self.new_temp();
+            self.emit(&format!(
+                "  {} = call i32 @_setjmp(i8* getelementptr inbounds ([256 x i8], [256 x i8]* @.test_jmp, i64 0, i64 0)) returns_twice",
+                jump
+            ));
+            let first_pass = self.new_temp();
+            self.emit(&format!("  {} = icmp eq i32 {}, 0", first_pass, jump));
+            self.emit(&format!("  br i1 {}, label %{}, label %{}", first_pass, run_label, failed_label));
+
+            self.emit(&format!("{}:", run_label));
+            let return_type = self.function_signatures.get(test).cloned().unwrap_or_else(|| "void".to_string());
+            if return_type == "void" {
+                self.emit(&format!("  call void @{}()", test));
+            } else {
+                let ignored = self.new_temp();
+                self.emit(&format!("  {} = call {} @{}()", ignored, return_type, test));
+            }
+            let ok_msg = self.gen_string_ptr(&format!("test {} ... ok\n", test));
+            let printed = self.new_temp();
+            self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {})", printed, ok_msg));
+            self.gen_increment_global("@.test_passed");
+            self.emit(&format!("  br label %{}", next_label));
+
+            self.emit(&format!("{}:", failed_label));
+            let failed_msg = self.gen_string_ptr(&format!("test {} ... FAILED\n", test));
+            let printed = self.new_temp();
+            self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {})", printed, failed_msg));
+            self.gen_increment_global("@.test_failed");
+            self.emit(&format!("  br label %{}", next_label));
+
+            self.emit(&format!("{}:", next_label));
+        }
+
+        let passed = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* @.test_passed", passed));
+        let failed = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* @.test_failed", failed));
+        let summary = self.gen_string_ptr("\ntest result: %lld passed, %lld failed\n");
+        let printed = self.new_temp();
+        self.emit(&format!("  {} = call i32 (i8*, ...) @printf(i8* {}, i64 {}, i64 {})", printed, summary, passed, failed));
+
+        let any_failed = self.new_temp();
+        self.emit(&format!("  {} = icmp ne i64 {}, 0", any_failed, failed));
+        let exit_code = self.new_temp();
+        self.emit(&format!("  {} = zext i1 {} to i32", exit_code, any_failed));
+        self.emit(&format!("  ret i32 {}", exit_code));
+        self.emit("}");
+    }
+
+    fn gen_increment_global(&mut self, global: &str) {
+        let current = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* {}", current, global));
+        let next = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, 1", next, current));
+        self.emit(&format!("  store i64 {}, i64* {}", next, global));
+    }
+
+    fn gen_string_ptr(&mut self, value: &str) -> String {
+        let id = self.new_string_literal(value);
+        let len = value.len() + 1;
+        let ptr = self.new_temp();
+        self.emit(&format!(
+            "  {} = getelementptr inbounds [{} x i8], [{} x i8]* @{}, i64 0, i64 0",
+            ptr, len, len, id
+        ));
+        ptr
+    }
+
+    /// Computes max(lo, min(value, hi)); when lo > hi the result is therefore lo.
+    fn gen_clamp(&mut self, value: &str, lo: &str, hi: &str, clamp_type: &str) -> String {
+        let (llvm_type, greater, less) = if clamp_type == "float" {
+            ("double", "fcmp ogt", "fcmp olt")
+        } else {
+            ("i64", "icmp sgt", "icmp slt")
+        };
+        let above = self.new_temp();
+        self.emit(&format!("  {} = {} {} {}, {}", above, greater, llvm_type, value, hi));
+        let capped = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, {} {}, {} {}", capped, above, llvm_type, hi, llvm_type, value));
+        let below = self.new_temp();
+        self.emit(&format!("  {} = {} {} {}, {}", below, less, llvm_type, capped, lo));
+        let result = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, {} {}, {} {}", result, below, llvm_type, lo, llvm_type, capped));
+        result
+    }
+
+    /// `lerp(a, b, t)`: `a + (b - a) * t` in `double`, so `t` outside 0..1 extrapolates.
+    fn gen_lerp(&mut self, a: &str, b: &str, t: &str) -> String {
+        let span = self.new_temp();
+        self.emit(&format!("  {} = fsub double {}, {}", span, b, a));
+        let scaled = self.new_temp();
+        self.emit(&format!("  {} = fmul double {}, {}", scaled, span, t));
+        let result = self.new_temp();
+        self.emit(&format!("  {} = fadd double {}, {}", result, a, scaled));
+        result
+    }
+
+    /// Calls `target` `count` times and returns the elapsed CPU time in milliseconds.
+    fn gen_bench(&mut self, count: &str, target: &str) -> String {
+        let symbol = self.resolve_function(target);
+        let return_type = self.function_signatures.get(&symbol).cloned().unwrap_or_else(|| "i64".to_string());
+        let cond_label = self.new_label("bench_cond");
+        let body_label = self.new_label("bench_body");
+        let end_label = self.new_label("bench_end");
+
+        let counter = self.new_slot();
+        self.emit(&format!("  {} = alloca i64", counter));
+        self.emit(&format!("  store i64 0, i64* {}", counter));
+        let start = self.new_temp();
+        self.emit(&format!("  {} = call i64 @clock()", start));
+        self.emit(&format!("  br label %{}", cond_label));
+
+        self.emit(&format!("{}:", cond_label));
+        let done = self.new_temp();
+        self.emit(&format!("  {} = load i64, i64* {}", done, counter));
+        let more = self.new_temp();
+        self.emit(&format!("  {} = icmp slt i64 {}, {}", more, done, count));
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", more, body_label, end_label));
+
+        self.emit(&format!("{}:", body_label));
+        if return_type == "void" {
+            self.emit(&format!("  call void @{}()", symbol));
+        } else {
+            let ignored = self.new_temp();
+            self.emit(&format!("  {} = call {} @{}()", ignored, return_type, symbol));
+        }
+        let next = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, 1", next, done));
+        self.emit(&format!("  store i64 {}, i64* {}", next, counter));
+        self.emit(&format!("  br label %{}", cond_label));
+
+        self.emit(&format!("{}:", end_label));
+        let end = self.new_temp();
+        self.emit(&format!("  {} = call i64 @clock()", end));
+        let elapsed = self.new_temp();
+        self.emit(&format!("  {} = sub i64 {}, {}", elapsed, end, start));
+        // POSIX fixes CLOCKS_PER_SEC at one million
+        let millis = self.new_temp();
+        self.emit(&format!("  {} = sdiv i64 {}, 1000", millis, elapsed));
+        millis
+    }
+
+    /// `lo <= value && value <= hi`, both bounds inclusive.
+    fn gen_between(&mut self, llvm_type: &str, value: &str, lo: &str, hi: &str) -> String {
+        let above_lo = self.new_temp();
+        self.emit(&format!("  {} = icmp sle {} {}, {}", above_lo, llvm_type, lo, value));
+        let below_hi = self.new_temp();
+        self.emit(&format!("  {} = icmp sle {} {}, {}", below_hi, llvm_type, value, hi));
+        let result = self.new_temp();
+        self.emit(&format!("  {} = and i1 {}, {}", result, above_lo, below_hi));
+        result
+    }
+
+    /// An arm body may end in `break`/`continue`/`return`, which already left the block.
+    fn gen_match_arm_body(&mut self, body: &AstNode, end_label: &str) {
+        self.block_terminated = false;
+        self.gen_node(body);
+        if !self.block_terminated {
+            self.emit(&format!("  br label %{}", end_label));
+        }
+        self.block_terminated = false;
+    }
+
+    /// `--annotate` comment naming the statement about to be generated.
+    fn emit_annotation(&mut self, stmt: &AstNode) {
+        let summary = match stmt {
+            AstNode::LetBinding { mutable: true, name, .. } => format!("let mut {}", name),
+            AstNode::LetBinding { name, .. } => format!("let {}", name),
+            AstNode::LetPattern { .. } => "let pattern".to_string(),
+            AstNode::Assignment { name, .. } => format!("{} = ...", name),
+            AstNode::ArrayAssignment { array, .. } => format!("{}[...] = ...", array),
+            AstNode::ExpressionStatement(expr) => match expr.as_ref() {
+                AstNode::Call { name, .. } => format!("{}(...)", name),
+                _ => "expression".to_string(),
+            },
+            AstNode::If { .. } => "if".to_string(),
+            AstNode::IfLet { .. } => "if let".to_string(),
+            AstNode::While { .. } => "while".to_string(),
+            AstNode::For { variable, .. } => format!("for {} in ...", variable),
+            AstNode::Match { .. } => "match".to_string(),
+            AstNode::Return(_) => "return".to_string(),
+            AstNode::Break { .. } => "break".to_string(),
+            AstNode::Continue { .. } => "continue".to_string(),
+            _ => return,
+        };
+
+        // Statements without a position of their own borrow their first operand's
+        let location = match stmt {
+            AstNode::ExpressionStatement(expr) => expr.location(),
+            AstNode::If { condition, .. } | AstNode::While { condition, .. } => condition.location(),
+            AstNode::IfLet { value, .. } => value.location(),
+            AstNode::Match { value, .. } => value.location(),
+            AstNode::Return(Some(value)) => value.location(),
+            _ => stmt.location(),
+        };
+        match location {
+            Some(location) => self.emit(&format!("  ; line {}: {}", location.line, summary)),
+            None => self.emit(&format!("  ; {}", summary)),
+        }
+    }
+
+    /// Under `--sanitize`, branches to a failure block that reports `problem` at the
+    /// current source position and exits when `failed` is true. With `args`,
+    /// `problem` is a printf format they are substituted into.
+    fn gen_runtime_check(&mut self, failed: &str, problem: &str, args: &str) {
+        let message = format!(
+            "runtime error at line {}, column {}: {}",
+            self.current_location.line, self.current_location.column, problem
+        );
+        let fail_label = self.new_label("check_fail");
+        let ok_label = self.new_label("check_ok");
+
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", failed, fail_label, ok_label));
+        self.emit(&format!("{}:", fail_label));
+        if args.is_empty() {
+            let id = self.new_string_literal(&message);
+            let len = message.len() + 1;
+            let ptr = self.new_temp();
+            self.emit(&format!("  {} = getelementptr inbounds [{} x i8], [{} x i8]* @{}, i64 0, i64 0", ptr, len, len, id));
+            self.emit(&format!("  call void @sanitize_fail(i8* {})", ptr));
+        } else {
+            self.gen_printf(&format!("{}\n", message), args);
+            self.emit("  call void @exit(i32 101)");
+        }
+        self.emit("  unreachable");
+        self.emit(&format!("{}:", ok_label));
+    }
+
+    /// An unsigned compare also rejects negative indices.
+    fn gen_bounds_check(&mut self, index: &str, size: &str) {
+        if !self.options.sanitize {
+            return;
+        }
+        let out_of_bounds = self.new_temp();
+        self.emit(&format!("  {} = icmp uge i64 {}, {}", out_of_bounds, index, size));
+        self.gen_runtime_check(
+            &out_of_bounds,
+            "index out of bounds: the len is %lld but the index is %lld",
+            &format!(", i64 {}, i64 {}", size, index),
+        );
+    }
+
+    /// `--sanitize` check for indexing a `[char]`, whose length is its `strlen`.
+    fn gen_chars_bounds_check(&mut self, chars: &str, index: &str) {
+        if !self.options.sanitize {
+            return;
+        }
+        let len = self.new_temp();
+        self.emit(&format!("  {} = call i64 @strlen(i8* {})", len, chars));
+        self.gen_bounds_check(index, &len);
+    }
+
+    /// `+`, `-` or `*` through LLVM's `llvm.<intrinsic>.with.overflow.i64`.
+    fn gen_checked_arithmetic(&mut self, intrinsic: &str, symbol: &str, left: &str, right: &str) -> String {
+        let pair = self.new_temp();
+        self.emit(&format!(
+            "  {} = call {{ i64, i1 }} @llvm.{}.with.overflow.i64(i64 {}, i64 {})",
+            pair, intrinsic, left, right
+        ));
+        let result = self.new_temp();
+        self.emit(&format!("  {} = extractvalue {{ i64, i1 }} {}, 0", result, pair));
+        let overflowed = self.new_temp();
+        self.emit(&format!("  {} = extractvalue {{ i64, i1 }} {}, 1", overflowed, pair));
+        self.gen_runtime_check(&overflowed, &format!("integer overflow in '{}'", symbol), "");
+        result
+    }
+
+    /// Floored modulo: the result takes the sign of the divisor, so mod(-7, 3) == 2.
+    /// Computed from `srem` by adding the divisor back when the remainder is
+    /// non-zero and its sign differs from the divisor's.
+    fn gen_floored_mod(&mut self, left: &str, right: &str) -> String {
+        let rem = self.new_temp();
+        self.emit(&format!("  {} = srem i64 {}, {}", rem, left, right));
+        let nonzero = self.new_temp();
+        self.emit(&format!("  {} = icmp ne i64 {}, 0", nonzero, rem));
+        let sign_bits = self.new_temp();
+        self.emit(&format!("  {} = xor i64 {}, {}", sign_bits, rem, right));
+        let signs_differ = self.new_temp();
+        self.emit(&format!("  {} = icmp slt i64 {}, 0", signs_differ, sign_bits));
+        let needs_adjust = self.new_temp();
+        self.emit(&format!("  {} = and i1 {}, {}", needs_adjust, nonzero, signs_differ));
+        let adjusted = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, {}", adjusted, rem, right));
+        let result = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, i64 {}, i64 {}", result, needs_adjust, adjusted, rem));
+        result
+    }
+
+    fn gen_format(&mut self, args: &[AstNode]) -> String {
+        let pieces = match &args[0] {
+            AstNode::StringLit(fmt) => parse_format_string(fmt).unwrap_or_default(),
+            _ => return "null".to_string(),
+        };
+
+        let mut c_format = String::new();
+        let mut c_args = Vec::new();
+        let mut values = args[1..].iter();
+
+        for piece in &pieces {
+            match piece {
+                FormatPiece::Literal(text) => c_format.push_str(&text.replace('%', "%%")),
+                FormatPiece::Placeholder(spec) => {
+                    let Some(arg) = values.next() else { break };
+                    let arg_type = self.infer_llvm_type(arg);
+                    let reg = self.gen_node(arg);
+
+                    // Mirror Rust's formatting: numbers are right-aligned, text is left-aligned
+                    let (flags, conversion, arg_str) = match arg_type.as_str() {
+                        "string" => ("-", "s", format!("i8* {}", reg)),
+                        "bool" => {
+                            let text = self.gen_bool_to_string(&reg);
+                            ("-", "s", format!("i8* {}", text))
+                        }
+                        "char" => {
+                            let widened = self.new_temp();
+                            self.emit(&format!("  {} = sext i8 {} to i32", widened, reg));
+                            ("-", "c", format!("i32 {}", widened))
+                        }
+                        // `{:.2}` fixes the number of decimals, otherwise the shortest form is used
+                        "float" => {
+                            let conversion = if spec.precision.is_some() { "f" } else { ".15g" };
+                            let shown = self.gen_float_for_display(&reg);
+                            (if spec.zero_pad { "0" } else { "" }, conversion, format!("double {}", shown))
+                        }
+                        _ => (if spec.zero_pad { "0" } else { "" }, "lld", format!("i64 {}", reg)),
+                    };
+
+                    c_format.push('%');
+                    if spec.width.is_some() {
+                        c_format.push_str(flags);
+                    }
+                    if let Some(width) = spec.width {
+                        c_format.push_str(&width.to_string());
+                    }
+                    if let Some(precision) = spec.precision
+                        && (conversion == "s" || conversion == "f")
+                    {
+                        c_format.push_str(&format!(".{}", precision));
+                    }
+                    c_format.push_str(conversion);
+                    c_args.push(arg_str);
+                }
+            }
+        }
+
+        let fmt_id = self.new_string_literal(&c_format);
+        let fmt_len = c_format.len() + 1;
+        let fmt_ptr = self.new_temp();
+        self.emit(&format!(
+            "  {} = getelementptr inbounds [{} x i8], [{} x i8]* @{}, i64 0, i64 0",
+            fmt_ptr, fmt_len, fmt_len, fmt_id
+        ));
+
+        let extra_args: String = c_args.iter().map(|a| format!(", {}", a)).collect();
+
+        let len = self.new_temp();
+        self.emit(&format!(
+            "  {} = call i32 (i8*, i64, i8*, ...) @snprintf(i8* null, i64 0, i8* {}{})",
+            len, fmt_ptr, extra_args
+        ));
+        let len_i64 = self.new_temp();
+        self.emit(&format!("  {} = sext i32 {} to i64", len_i64, len));
+        let size = self.new_temp();
+        self.emit(&format!("  {} = add i64 {}, 1", size, len_i64));
+
+        let buffer = self.new_temp();
+        self.emit(&format!("  {} = call i8* @malloc(i64 {})", buffer, size));
+        let written = self.new_temp();
+        self.emit(&format!(
+            "  {} = call i32 (i8*, i8*, ...) @sprintf(i8* {}, i8* {}{})",
+            written, buffer, fmt_ptr, extra_args
+        ));
+
+        buffer
+    }
+
+    fn gen_bool_to_string(&mut self, reg: &str) -> String {
+        let true_id = self.new_string_literal("true");
+        let false_id = self.new_string_literal("false");
+
+        let true_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr inbounds [5 x i8], [5 x i8]* @{}, i64 0, i64 0", true_ptr, true_id));
+        let false_ptr = self.new_temp();
+        self.emit(&format!("  {} = getelementptr inbounds [6 x i8], [6 x i8]* @{}, i64 0, i64 0", false_ptr, false_id));
+
+        let result = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, i8* {}, i8* {}", result, reg, true_ptr, false_ptr));
+        result
+    }
+
+    /// Replaces any NaN with the positive quiet NaN, since printf shows the sign
+    /// of a NaN and `0.0 / 0.0` produces a negative one on x86.
+    fn gen_float_for_display(&mut self, reg: &str) -> String {
+        let is_nan = self.new_temp();
+        self.emit(&format!("  {} = fcmp uno double {}, {}", is_nan, reg, reg));
+        let shown = self.new_temp();
+        self.emit(&format!("  {} = select i1 {}, double 0x7FF8000000000000, double {}", shown, is_nan, reg));
+        shown
+    }
+
     fn infer_llvm_type(&self, node: &AstNode) -> String {
         match node {
             AstNode::Number(_) => "int".to_string(),
+            AstNode::Float(_) => "float".to_string(),
             AstNode::Boolean(_) => "bool".to_string(),
             AstNode::Character(_) => "char".to_string(),
             AstNode::StringLit(_) => "string".to_string(),
-            AstNode::BinaryOp { left, .. } => self.infer_llvm_type(left),
+            AstNode::MethodCall { method, .. } if method == "contains" => "bool".to_string(),
+            AstNode::BinaryOp {
+                op: BinOp::Equal | BinOp::NotEqual | BinOp::LessThan | BinOp::LessEqual
+                    | BinOp::GreaterThan | BinOp::GreaterEqual | BinOp::And | BinOp::Or,
+                ..
+            } => "bool".to_string(),
+            AstNode::BinaryOp { left, .. } if self.infer_llvm_type(left) == "string" => "string".to_string(),
+            AstNode::BinaryOp { left, right, .. }
+                if self.infer_llvm_type(left) == "float" || self.infer_llvm_type(right) == "float" =>
+            {
+                "float".to_string()
+            }
+            AstNode::BinaryOp { .. } => "int".to_string(),
+            AstNode::IfExpr { then_value, else_value, .. } => {
+                let then_type = self.infer_llvm_type(then_value);
+                if then_type == "int" && self.infer_llvm_type(else_value) == "float" {
+                    "float".to_string()
+                } else {
+                    then_type
+                }
+            }
+            AstNode::UnaryOp { op: UnOp::Not, .. } => "bool".to_string(),
+            AstNode::UnaryOp { operand, .. } if self.infer_llvm_type(operand) == "float" => "float".to_string(),
             AstNode::Identifier { name, .. } => {
                 self.current_function_vars
                     .get(name)
                     .map(|m| m.var_type.clone())
                     .unwrap_or_else(|| "int".to_string())
             }
+            AstNode::Index { array, .. } => match self.infer_llvm_type(array).as_str() {
+                "[string]" => "string".to_string(),
+                "[char]" => "char".to_string(),
+                _ => self.array_element_type(array).unwrap_or_else(|| "int".to_string()),
+            },
             AstNode::ArrayLit(_) => "array".to_string(),
+            AstNode::EnumValue { enum_name, .. } if self.enum_types.contains_key(enum_name) => enum_name.clone(),
             AstNode::EnumValue { .. } => "enum".to_string(),
-            AstNode::Call { name, .. } => {
+            AstNode::StructInit { name, .. } => name.clone(),
+            AstNode::MemberAccess { object, field } => self
+                .struct_field(&self.infer_llvm_type(object), field)
+                .map(|(_, field_type)| field_type)
+                .unwrap_or_else(|| "int".to_string()),
+            AstNode::Call { name, args, .. } => {
                 match name.as_str() {
                     "read_file" => "string".to_string(),
+                    "format" => "string".to_string(),
+                    "to_chars" => "[char]".to_string(),
+                    "from_chars" => "string".to_string(),
+                    "between" => "bool".to_string(),
+                    "clamp" if args.iter().any(|arg| self.infer_llvm_type(arg) == "float") => "float".to_string(),
+                    "lerp" => "float".to_string(),
                     "write_file" => "int".to_string(),
                     "puts" => "int".to_string(),
                     "print_int" => "int".to_string(),
-                    _ => "int".to_string(),
+                    _ => self.function_return_types
+                        .get(&self.resolve_function(name))
+                        .cloned()
+                        .unwrap_or_else(|| "int".to_string()),
                 }
             }
             _ => "int".to_string(),
         }
     }
 
+    /// Booleans are `i1`, chars `i8` and ints `i64`. Brings both operands of `op` to
+    /// the width it works on: `i64` for arithmetic, `i8` for comparing two chars and
+    /// `i64` for any other comparison. Bools are widened too: the signed predicates
+    /// would read an `i1` true as -1 and order it below false.
+    /// Returns the converted registers and the width to compare at.
+    fn unify_operands(
+        &mut self,
+        op: &BinOp,
+        left: &AstNode,
+        right: &AstNode,
+        left_reg: String,
+        right_reg: String,
+    ) -> (String, String, &'static str) {
+        let left_type = self.infer_llvm_type(left);
+        let right_type = self.infer_llvm_type(right);
+        let is_comparison = matches!(
+            op,
+            BinOp::Equal | BinOp::NotEqual | BinOp::LessThan | BinOp::LessEqual
+                | BinOp::GreaterThan | BinOp::GreaterEqual
+        );
+        match (left_type.as_str(), right_type.as_str()) {
+            ("char", "char") if is_comparison => (left_reg, right_reg, "i8"),
+            ("bool" | "char" | "int", "bool" | "char" | "int") => {
+                let left_reg = self.coerce(&left_reg, &left_type, "int");
+                let right_reg = self.coerce(&right_reg, &right_type, "int");
+                (left_reg, right_reg, "i64")
+            }
+            _ => (left_reg, right_reg, "i64"),
+        }
+    }
+
+    /// Arithmetic and comparisons with a `float` operand. The other operand is
+    /// converted to `double` first, so `1 + 0.5` is `1.5`.
+    fn gen_float_binary(&mut self, op: &BinOp, left: &AstNode, right: &AstNode, left_reg: &str, right_reg: &str) -> String {
+        let left_type = self.infer_llvm_type(left);
+        let right_type = self.infer_llvm_type(right);
+        let left_reg = self.coerce(left_reg, &left_type, "float");
+        let right_reg = self.coerce(right_reg, &right_type, "float");
+
+        let instruction = match op {
+            BinOp::Add => "fadd",
+            BinOp::Sub => "fsub",
+            BinOp::Mul => "fmul",
+            BinOp::Div => "fdiv",
+            BinOp::Mod => "frem",
+            BinOp::Equal => "fcmp oeq",
+            BinOp::NotEqual => "fcmp une",
+            BinOp::LessThan => "fcmp olt",
+            BinOp::LessEqual => "fcmp ole",
+            BinOp::GreaterThan => "fcmp ogt",
+            BinOp::GreaterEqual => "fcmp oge",
+            BinOp::Pow => {
+                let result = self.new_temp();
+                self.emit(&format!(
+                    "  {} = call double @llvm.pow.f64(double {}, double {})",
+                    result, left_reg, right_reg
+                ));
+                return result;
+            }
+            BinOp::And | BinOp::Or => unreachable!("handled by gen_short_circuit"),
+        };
+        let result = self.new_temp();
+        self.emit(&format!("  {} = {} double {}, {}", result, instruction, left_reg, right_reg));
+        result
+    }
+
+    /// `&&` and `||`: the right operand is only evaluated when the left one does not
+    /// already decide the result.
+    fn gen_short_circuit(&mut self, is_and: bool, left: &AstNode, right: &AstNode) -> String {
+        let rhs_label = self.new_label(if is_and { "and_rhs" } else { "or_rhs" });
+        let end_label = self.new_label(if is_and { "and_end" } else { "or_end" });
+
+        let left_reg = self.gen_condition(left);
+        let left_block = self.current_block.clone();
+        let (on_true, on_false) = if is_and { (&rhs_label, &end_label) } else { (&end_label, &rhs_label) };
+        self.emit(&format!("  br i1 {}, label %{}, label %{}", left_reg, on_true, on_false));
+
+        self.emit(&format!("{}:", rhs_label));
+        let right_reg = self.gen_condition(right);
+        // The right operand may have opened blocks of its own
+        let right_block = self.current_block.clone();
+        self.emit(&format!("  br label %{}", end_label));
+
+        self.emit(&format!("{}:", end_label));
+        let result = self.new_temp();
+        let decided = if is_and { "false" } else { "true" };
+        self.emit(&format!("  {} = phi i1 [ {}, %{} ], [ {}, %{} ]", result, decided, left_block, right_reg, right_block));
+        result
+    }
+
+    /// Converts a scalar register between `bool` (`i1`), `char` (`i8`), `int`
+    /// (`i64`) and `float` (`double`); anything else is returned unchanged.
+    fn coerce(&mut self, reg: &str, from: &str, to: &str) -> String {
+        let instruction = match (from, to) {
+            ("bool", "int") => format!("zext i1 {} to i64", reg),
+            ("char", "int") => format!("sext i8 {} to i64", reg),
+            ("int", "bool") => format!("icmp ne i64 {}, 0", reg),
+            ("int", "char") => format!("trunc i64 {} to i8", reg),
+            ("bool", "char") => format!("zext i1 {} to i8", reg),
+            ("char", "bool") => format!("icmp ne i8 {}, 0", reg),
+            ("float", "bool") => format!("fcmp une double {}, 0.0", reg),
+            ("int", "float") => format!("sitofp i64 {} to double", reg),
+            ("bool" | "char", "float") => {
+                let widened = self.coerce(reg, from, "int");
+                format!("sitofp i64 {} to double", widened)
+            }
+            _ => return reg.to_string(),
+        };
+        let result = self.new_temp();
+        self.emit(&format!("  {} = {}", result, instruction));
+        result
+    }
+
+    /// Generates a branch condition as an `i1`, testing an integer against zero.
+    fn gen_condition(&mut self, condition: &AstNode) -> String {
+        let reg = self.gen_node(condition);
+        let condition_type = self.infer_llvm_type(condition);
+        self.coerce(&reg, &condition_type, "bool")
+    }
+
+    /// Peephole for integer identities: `x + 0`, `0 + x`, `x - 0`, `x * 1`,
+    /// `1 * x` and `x / 1` yield the other operand without emitting an instruction.
+    fn arithmetic_identity(op: &BinOp, left: &str, right: &str) -> Option<String> {
+        match (op, left, right) {
+            (BinOp::Add, _, "0") | (BinOp::Sub, _, "0") | (BinOp::Mul, _, "1") | (BinOp::Div, _, "1") => {
+                Some(left.to_string())
+            }
+            (BinOp::Add, "0", _) | (BinOp::Mul, "1", _) => Some(right.to_string()),
+            _ => None,
+        }
+    }
+
+    /// Tag stored for `variant`: its position in the enum's declaration.
+    fn variant_tag(&self, enum_name: &str, variant: &str) -> usize {
+        self.enum_types
+            .get(enum_name)
+            .and_then(|variants| variants.iter().position(|v| v.name == variant))
+            .unwrap_or(0)
+    }
+
+    /// Position and declared type of `field` in struct `struct_name`.
+    fn struct_field(&self, struct_name: &str, field: &str) -> Option<(usize, String)> {
+        let fields = self.struct_types.get(struct_name)?;
+        let index = fields.iter().position(|f| f.name == field)?;
+        Some((index, fields[index].field_type.clone()))
+    }
+
+    /// Byte size of a type as laid out by this code generator. Arrays are always
+    /// lowered as `[N x i64]`, and struct sizes are the sum of their fields.
+    fn size_of_type(&self, type_name: &str) -> usize {
+        match type_name {
+            "int" | "string" => 8,
+            "bool" | "char" => 1,
+            _ if type_name.starts_with('[') => {
+                let count = type_name
+                    .split(';')
+                    .nth(1)
+                    .and_then(|n| n.trim().trim_end_matches(']').trim().parse::<usize>().ok())
+                    .unwrap_or(0);
+                count * 8
+            }
+            _ if self.enum_types.contains_key(type_name) => 16,
+            _ => match self.struct_types.get(type_name) {
+                Some(fields) => fields.iter().map(|f| self.size_of_type(&f.field_type)).sum(),
+                None => 8,
+            },
+        }
+    }
+
     fn type_to_llvm(&self, type_name: &str) -> &str {
         match type_name {
             "int" => "i64",
+            "float" => "double",
             "bool" => "i1",
             "char" => "i8",
             "string" => "i8*",
             "array" => "i64*",
             "enum" => "{ i32, i64 }*",
-            _ => "i64",
+            "[string]" => "i8**",
+            "[char]" => "i8*",
+            _ if self.enum_types.contains_key(type_name) => "{ i32, i64 }*",
+            _ => match self.struct_pointer_types.get(type_name) {
+                Some(pointer_type) => pointer_type,
+                None => "i64",
+            },
         }
     }
 
@@ -945,6 +3129,13 @@ impl CodeGenerator {
         temp
     }
 
+    /// A stack slot for `alloca`; `gen_function` hoists these into the entry block.
+    fn new_slot(&mut self) -> String {
+        let slot = format!("%slot{}", self.slot_counter);
+        self.slot_counter += 1;
+        slot
+    }
+
     fn new_label(&mut self, prefix: &str) -> String {
         let label = format!("{}{}", prefix, self.label_counter);
         self.label_counter += 1;
@@ -959,21 +3150,48 @@ impl CodeGenerator {
     }
 
     fn emit(&mut self, line: &str) {
+        if self.options.optimize {
+            self.invalidate_load_cache(line);
+        }
+        if let Some(label) = line.strip_suffix(':')
+            && !line.starts_with(' ')
+        {
+            self.current_block = label.to_string();
+        }
         self.output.push_str(line);
         self.output.push('\n');
     }
 
+    fn invalidate_load_cache(&mut self, line: &str) {
+        let instruction = line.trim_start();
+
+        // A label starts a new basic block, and a call may write through any pointer
+        if !line.starts_with(' ') || instruction.contains("call ") {
+            self.load_cache.clear();
+        } else if instruction.starts_with("store ")
+            && let Some(ptr) = instruction.rsplit(' ').next()
+        {
+            // Reference parameters may alias each other, so forget everything
+            if ptr.starts_with("%arg_") {
+                self.load_cache.clear();
+            } else {
+                self.load_cache.remove(ptr);
+            }
+        }
+    }
+
+    /// Escapes a string for an LLVM `c"..."` constant. Works on UTF-8 bytes rather
+    /// than chars so the emitted bytes always agree with the `[len x i8]` type,
+    /// which is computed from `str::len()`. An embedded `\0` becomes `\00` like any
+    /// other non-printable byte, so the constant is not cut short.
     fn escape_string(&self, s: &str) -> String {
         let mut escaped = String::new();
-        for c in s.bytes() { 
-            match c {
-                b'\n' => escaped.push_str("\\0A"), 
-                b'\r' => escaped.push_str("\\0D"), 
-                b'\t' => escaped.push_str("\\09"), 
-                b'\\' => escaped.push_str("\\5C"), 
-                b'\"' => escaped.push_str("\\22"), 
-                32..=126 => escaped.push(c as char), 
-                _ => escaped.push_str(&format!("\\{:02x}", c)), 
+        for byte in s.bytes() {
+            match byte {
+                b'\\' => escaped.push_str("\\5C"),
+                b'\"' => escaped.push_str("\\22"),
+                32..=126 => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{:02X}", byte)),
             }
         }
         escaped
@@ -982,4 +3200,252 @@ impl CodeGenerator {
     fn build_output(&self) -> String {
         self.output.clone()
     }
-}
\ No newline at end of file
+}
+
+/// Types `coerce` converts between.
+fn is_scalar(type_name: &str) -> bool {
+    matches!(type_name, "int" | "float" | "bool" | "char")
+}
+
+/// Whether `node` contains `return name(...)`.
+fn has_self_tail_call(node: &AstNode, name: &str) -> bool {
+    match node {
+        AstNode::Return(Some(value)) => matches!(value.as_ref(), AstNode::Call { name: callee, .. } if callee == name),
+        AstNode::Block(statements) => statements
+            .iter()
+            .any(|statement| !matches!(statement, AstNode::FunctionDef { .. }) && has_self_tail_call(statement, name)),
+        AstNode::If { then_block, else_block, .. }
+        | AstNode::IfLet { then_block, else_block, .. }
+        | AstNode::While { body: then_block, else_block, .. } => {
+            has_self_tail_call(then_block, name)
+                || else_block.as_deref().is_some_and(|block| has_self_tail_call(block, name))
+        }
+        AstNode::For { body, .. } => has_self_tail_call(body, name),
+        AstNode::Match { arms, .. } => arms.iter().any(|arm| has_self_tail_call(&arm.body, name)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_to_ir;
+
+    fn ir_with(source: &str, options: CodegenOptions) -> String {
+        compile_to_ir(source, options).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn ir(source: &str) -> String {
+        ir_with(source, CodegenOptions::default())
+    }
+
+    /// The body of the function `name` in `ir`.
+    fn function<'a>(ir: &'a str, name: &str) -> &'a str {
+        let start = ir.find(&format!(" @{}(", name)).unwrap_or_else(|| panic!("no function '{}'", name));
+        let end = ir[start..].find("\n}\n").unwrap() + start;
+        &ir[start..end]
+    }
+
+    fn main_ir(body: &str) -> String {
+        ir(&format!("fn main() {{\n{}\n}}\n", body))
+    }
+
+    #[test]
+    fn void_calls_have_no_result() {
+        let ir = ir("fn hello() {\nprint(1);\n}\nfn main() {\nhello();\n}\n");
+        assert!(function(&ir, "main").lines().any(|line| line.trim() == "call void @hello()"));
+    }
+
+    #[test]
+    fn optimize_reuses_loads() {
+        let source = "fn main() {\nlet x = 5;\nlet y = x + x;\nprint(y);\n}\n";
+        let loads = |ir: &str| function(ir, "main").matches("load i64").count();
+        let optimized = ir_with(source, CodegenOptions { optimize: true, ..Default::default() });
+        assert_eq!(loads(&optimized) + 1, loads(&ir(source)));
+    }
+
+    #[test]
+    fn adding_zero_emits_no_add() {
+        let ir = main_ir("let x = 5;\nlet y = x + 0;\nprint(y);");
+        assert!(!function(&ir, "main").contains(" add "));
+    }
+
+    #[test]
+    fn numbering_restarts_in_each_function() {
+        let b = "fn b(n: int) -> int {\nif n > 0 {\nreturn n * 2;\n}\nreturn 0;\n}\nfn main() {\nprint(a() + b(1));\n}\n";
+        let small = ir(&format!("fn a() -> int {{\nreturn 1;\n}}\n{}", b));
+        let large = ir(&format!("fn a() -> int {{\nif 1 < 2 {{\nreturn 1 + 2 * 3;\n}}\nreturn 0;\n}}\n{}", b));
+        assert_eq!(function(&small, "b"), function(&large, "b"));
+    }
+
+    #[test]
+    fn literal_string_length_is_folded() {
+        let ir = main_ir("print(\"hi\".len());\nprintln(len(\"a\\0b\"));");
+        let main = function(&ir, "main");
+        assert!(!main.contains("@strlen"));
+        assert!(main.contains("i64 2)"));
+        assert!(main.contains("i64 1)"));
+    }
+
+    #[test]
+    fn multibyte_string_constants_count_bytes() {
+        let ir = main_ir("print(\"hé\");");
+        assert!(ir.contains(r#"constant [4 x i8] c"h\C3\A9\00""#));
+    }
+
+    #[test]
+    fn enum_equality_compares_tags_and_payloads() {
+        let ir = ir("enum Color { Red, Green }\nfn main() {\nlet a = Color::Red;\nlet b = Color::Red;\nprint(a == b);\n}\n");
+        let main = function(&ir, "main");
+        assert!(main.contains("icmp eq i32"));
+        assert!(!main.contains("icmp eq { i32, i64 }*"));
+    }
+
+    #[test]
+    fn functions_may_be_called_before_their_definition() {
+        let ir = ir("fn main() {\nprintln(later(1));\n}\nfn later(n: int) -> int {\nreturn n;\n}\n");
+        assert!(function(&ir, "main").contains("call i64 @later(i64 1)"));
+    }
+
+    #[test]
+    fn enums_may_be_used_before_their_definition() {
+        let ir = ir("fn main() {\nlet c = Color::Green;\nprintln(c);\n}\nenum Color { Red, Green }\n");
+        assert!(function(&ir, "main").contains("store i32 1"));
+    }
+
+    #[test]
+    fn unannotated_value_functions_return_int() {
+        let ir = ir("fn two() {\nreturn 2;\n}\nfn main() {\nprintln(two() + 1);\n}\n");
+        assert!(ir.contains("define i64 @two()"));
+    }
+
+    #[test]
+    fn unannotated_functions_return_the_type_of_their_first_return() {
+        let ir = ir("fn name() {\nreturn \"bob\";\n}\nfn ready(n: int) {\nreturn n > 2;\n}\n\
+            fn main() {\nprintln(name());\nprintln(ready(3));\n}\n");
+        assert!(ir.contains("define i8* @name()"));
+        assert!(ir.contains("define i1 @ready(i64 %arg_n)"));
+    }
+
+    #[test]
+    fn string_ordering_uses_strcmp() {
+        let ir = main_ir("println(\"a\" < \"b\");\nprintln(\"a\" >= \"b\");");
+        let main = function(&ir, "main");
+        assert!(main.contains("icmp slt i32"));
+        assert!(main.contains("icmp sge i32"));
+        assert_eq!(main.matches("@strcmp").count(), 2);
+    }
+
+    #[test]
+    fn bools_compare_unsigned() {
+        let ir = main_ir("println(true < false);");
+        let main = function(&ir, "main");
+        assert!(main.contains("zext i1 1 to i64"));
+        assert!(!main.contains("icmp slt i1"));
+    }
+
+    #[test]
+    fn structs_lower_to_named_types() {
+        let ir = ir("struct Pt { x: int; y: int; }\nfn main() {\nlet p = Pt { x: 1, y: 2 };\nprintln(p.x + p.y);\n}\n");
+        assert!(ir.contains("%struct.Pt = type { i64, i64 }"));
+    }
+
+    #[test]
+    fn structs_print_their_fields() {
+        let ir = ir("struct Pt { x: int; y: int; }\nfn main() {\nlet p = Pt { x: 1, y: 2 };\nprintln(p);\n}\n");
+        assert!(ir.contains(r#"c"Pt { \00""#));
+        assert!(ir.contains(r#"c", y: \00""#));
+    }
+
+    #[test]
+    fn float_arithmetic_uses_double_instructions() {
+        let ir = main_ir("let f = 1.5 * 2.0;\nprintln(f);");
+        assert!(function(&ir, "main").contains("fmul double"));
+    }
+
+    #[test]
+    fn float_annotation_converts_int_values() {
+        let ir = main_ir("let f: float = 1;\nprintln(f);");
+        assert!(function(&ir, "main").contains("sitofp i64 1 to double"));
+    }
+
+    #[test]
+    fn printed_nan_has_no_sign() {
+        let ir = main_ir("let z = 0.0;\nprintln(z / z);\nprintln(format(\"{}\", z / z));");
+        let main = function(&ir, "main");
+        assert_eq!(main.matches("fcmp uno double").count(), 2);
+        assert!(main.contains("double 0x7FF8000000000000"));
+    }
+
+    #[test]
+    fn clamp_and_lerp_on_floats() {
+        let ir = main_ir("println(clamp(0.7, 0.0, 0.5));\nprintln(lerp(0.0, 10.0, 0.5));\nprintln(clamp(5, 0, 3));");
+        let main = function(&ir, "main");
+        assert!(main.contains("fcmp ogt double"));
+        assert!(main.contains("fmul double"));
+        assert!(main.contains("icmp sgt i64"));
+    }
+
+    #[test]
+    fn self_tail_calls_become_jumps() {
+        let ir = ir("fn count(n: int) -> int {\nif n == 0 {\nreturn 0;\n}\nreturn count(n - 1);\n}\nfn main() {\nprintln(count(1000000));\n}\n");
+        let count = function(&ir, "count");
+        assert!(!count.contains("call i64 @count"));
+        assert!(count.contains("br label %tail_entry"));
+        // Every stack slot is allocated once, before the loop header
+        let header = count.find("tail_entry0:").unwrap();
+        assert!(!count[header..].contains("alloca"));
+    }
+
+    #[test]
+    fn bounds_checks_only_under_sanitize() {
+        let source = "fn main() {\nlet a = [1, 2, 3];\nlet i = 1;\nprintln(a[i]);\nlet s = to_chars(\"hi\");\nprintln(s[i]);\n}\n";
+        assert!(!ir(source).contains("out of bounds"));
+        let sanitized = ir_with(source, CodegenOptions { sanitize: true, ..Default::default() });
+        assert_eq!(sanitized.matches("index out of bounds").count(), 2);
+        // The char array's length comes from its terminator
+        assert!(function(&sanitized, "main").contains("@strlen"));
+    }
+
+    #[test]
+    fn annotate_comments_statements() {
+        let source = "fn main() {\nlet x = 1;\nprint(x);\n}\n";
+        assert!(!ir(source).contains("; line"));
+        let annotated = ir_with(source, CodegenOptions { annotate: true, ..Default::default() });
+        assert!(annotated.contains("; line 2: let x"));
+        assert!(annotated.contains("; line 3: print(...)"));
+    }
+
+    #[test]
+    fn test_mode_runs_test_functions() {
+        let source = "fn test_ok() {\nassert_eq(1, 1);\n}\nfn test_bad() {\nassert_eq(1, 2);\n}\nfn main() {}\n";
+        let ir = ir_with(source, CodegenOptions { test_mode: true, ..Default::default() });
+        let main = function(&ir, "main");
+        assert!(main.contains("call void @test_ok()"));
+        assert!(main.contains("call void @test_bad()"));
+    }
+
+    #[test]
+    fn enum_values_are_freed_once() {
+        let source = "enum Color { Red, Green }\n\
+            fn same(c: Color) -> Color {\nreturn c;\n}\n\
+            fn main() {\nlet a = Color::Red;\nlet b = a;\nlet c = same(b);\nprintln(c);\n}\n";
+        let ir = ir(source);
+        // `a` moved into `b`, and `c` is a copy of `b`
+        assert_eq!(function(&ir, "main").matches("call void @free").count(), 2);
+        assert!(function(&ir, "same").contains("call i8* @malloc(i64 16)"));
+    }
+
+    #[test]
+    fn float_payloads_are_bitcast() {
+        let source = "enum Shape { Circle(float), Dot }\n\
+            fn main() {\n\
+            let s = Shape::Circle(1.5);\n\
+            match s { Shape::Circle(r) => { println(r * 2.0); } Shape::Dot => {} }\n\
+            }\n";
+        let main_ir = ir(source);
+        let main = function(&main_ir, "main");
+        assert!(main.contains("bitcast double"));
+        assert!(main.contains("bitcast i64"));
+    }
+}
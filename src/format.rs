@@ -0,0 +1,134 @@
+#[derive(Debug, Clone, PartialEq)]
+pub enum FormatPiece {
+    Literal(String),
+    Placeholder(FormatSpec),
+}
+
+/// A parsed `{...}` placeholder: `{}`, `{:5}`, `{:.2}`, `{:08}` or a combination
+/// such as `{:08.3}`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FormatSpec {
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+}
+
+pub fn parse_format_string(fmt: &str) -> Result<Vec<FormatPiece>, String> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut chars = fmt.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                literal.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                literal.push('}');
+            }
+            '{' => {
+                let mut spec = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => spec.push(c),
+                        None => return Err(format!("unterminated placeholder '{{{}' in format string", spec)),
+                    }
+                }
+
+                if !literal.is_empty() {
+                    pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(FormatPiece::Placeholder(parse_spec(&spec)?));
+            }
+            '}' => return Err("unmatched '}' in format string (use '}}' for a literal brace)".to_string()),
+            _ => literal.push(ch),
+        }
+    }
+
+    if !literal.is_empty() {
+        pieces.push(FormatPiece::Literal(literal));
+    }
+
+    Ok(pieces)
+}
+
+pub fn placeholder_count(pieces: &[FormatPiece]) -> usize {
+    pieces.iter().filter(|p| matches!(p, FormatPiece::Placeholder(_))).count()
+}
+
+fn parse_spec(spec: &str) -> Result<FormatSpec, String> {
+    let unknown = || format!("unknown format spec '{{{}}}'", spec);
+
+    if spec.is_empty() {
+        return Ok(FormatSpec::default());
+    }
+
+    let Some(body) = spec.strip_prefix(':') else {
+        return Err(unknown());
+    };
+
+    let (width_part, precision_part) = match body.split_once('.') {
+        Some((width, precision)) => (width, Some(precision)),
+        None => (body, None),
+    };
+
+    let zero_pad = width_part.len() > 1 && width_part.starts_with('0');
+    let width_digits = if zero_pad { &width_part[1..] } else { width_part };
+
+    let width = if width_digits.is_empty() {
+        None
+    } else {
+        Some(parse_digits(width_digits).ok_or_else(unknown)?)
+    };
+
+    let precision = match precision_part {
+        Some(digits) => Some(parse_digits(digits).ok_or_else(unknown)?),
+        None => None,
+    };
+
+    Ok(FormatSpec { zero_pad, width, precision })
+}
+
+fn parse_digits(digits: &str) -> Option<usize> {
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spec(zero_pad: bool, width: Option<usize>, precision: Option<usize>) -> FormatPiece {
+        FormatPiece::Placeholder(FormatSpec { zero_pad, width, precision })
+    }
+
+    #[test]
+    fn width_and_precision() {
+        assert_eq!(parse_format_string("{:.2}").unwrap(), vec![spec(false, None, Some(2))]);
+        assert_eq!(parse_format_string("{:5}").unwrap(), vec![spec(false, Some(5), None)]);
+        assert_eq!(parse_format_string("{:08.3}").unwrap(), vec![spec(true, Some(8), Some(3))]);
+    }
+
+    #[test]
+    fn literals_and_escaped_braces() {
+        assert_eq!(parse_format_string("a{{b}}{}c").unwrap(), vec![
+            FormatPiece::Literal("a{b}".to_string()),
+            spec(false, None, None),
+            FormatPiece::Literal("c".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn unsupported_specs() {
+        assert!(parse_format_string("{:x}").unwrap_err().contains("unknown format spec '{:x}'"));
+        assert!(parse_format_string("{name}").is_err());
+        assert!(parse_format_string("{:.}").is_err());
+        assert!(parse_format_string("{").unwrap_err().contains("unterminated placeholder"));
+        assert!(parse_format_string("}").unwrap_err().contains("unmatched '}'"));
+    }
+}
@@ -1,5 +1,3 @@
-use std::fmt;
-
 #[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     // Keywords
@@ -17,17 +15,21 @@ pub enum TokenType {
     Return,
     Break,
     Continue,
+    Import,
+    Alias,
     True,
     False,
     
     // Types
     IntType,
+    FloatType,
     BoolType,
     StringType,
     CharType,
     
     // Literals
     Number(i64),
+    Float(f64),
     StringLit(String),
     CharLit(char),
     Identifier(String),
@@ -36,6 +38,7 @@ pub enum TokenType {
     Plus,
     Minus,
     Star,
+    StarStar,
     Slash,
     Percent,
     Assign,
@@ -64,6 +67,10 @@ pub enum TokenType {
     Arrow,
     FatArrow,
     DotDot,
+    Question,
+    At,
+    /// One `//!` line from the top of the file, without the `//!` marker
+    ModuleDoc(String),
     
     // Special
     Eof,
@@ -83,6 +90,9 @@ pub struct Lexer<'a> {
     current: usize,
     line: usize,
     column: usize,
+    /// Whether only whitespace, comments and `//!` lines have been seen so far
+    at_module_start: bool,
+    warnings: Vec<String>,
 }
 
 impl<'a> Lexer<'a> {
@@ -94,21 +104,34 @@ impl<'a> Lexer<'a> {
             current: 0,
             line: 1,
             column: 1,
+            at_module_start: true,
+            warnings: Vec::new(),
         }
     }
+
+    /// Warnings found by `tokenize`, e.g. misplaced `//!` comments.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
     
     pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
         let mut tokens = Vec::new();
         
         while !self.is_at_end() {
-            self.skip_whitespace_and_comments();
+            self.skip_whitespace_and_comments()?;
             
             if self.is_at_end() {
                 break;
             }
             
+            if self.at_module_doc() {
+                tokens.push(self.read_module_doc());
+                continue;
+            }
+
             let token = self.next_token()?;
             tokens.push(token);
+            self.at_module_start = false;
         }
         
         tokens.push(Token {
@@ -141,7 +164,12 @@ impl<'a> Lexer<'a> {
             }
             '*' => {
                 self.advance();
-                TokenType::Star
+                if self.peek() == '*' {
+                    self.advance();
+                    TokenType::StarStar
+                } else {
+                    TokenType::Star
+                }
             }
             '/' => {
                 self.advance();
@@ -244,6 +272,14 @@ impl<'a> Lexer<'a> {
                 self.advance();
                 TokenType::Comma
             }
+            '?' => {
+                self.advance();
+                TokenType::Question
+            }
+            '@' => {
+                self.advance();
+                TokenType::At
+            }
             '.' => {
                 self.advance();
                 if self.peek() == '.' {
@@ -254,8 +290,9 @@ impl<'a> Lexer<'a> {
                 }
             }
             '"' => self.read_string()?,
+            'r' if self.at_raw_string() => self.read_raw_string()?,
             '\'' => self.read_char()?,
-            _ if ch.is_ascii_digit() => self.read_number(),
+            _ if ch.is_ascii_digit() => self.read_number()?,
             _ if ch.is_alphabetic() || ch == '_' => self.read_identifier(),
             _ => {
                 return Err(self.error_with_context(&format!("Unexpected character '{}'", ch)));
@@ -280,13 +317,33 @@ impl<'a> Lexer<'a> {
             }
             if self.peek() == '\\' {
                 self.advance();
+                if self.is_at_end() {
+                    break;
+                }
+                // A backslash at the end of a line continues the string on the next
+                // line without inserting a newline
+                if self.peek() == '\n' || (self.peek() == '\r' && self.peek_ahead(1) == '\n') {
+                    if self.peek() == '\r' {
+                        self.advance();
+                    }
+                    self.advance();
+                    self.line += 1;
+                    self.column = 1;
+                    continue;
+                }
+                if self.peek() == 'x' {
+                    value.push(self.read_hex_escape()?);
+                    continue;
+                }
                 let escaped = match self.peek() {
                     'n' => '\n',
                     't' => '\t',
                     'r' => '\r',
+                    '0' => '\0',
                     '\\' => '\\',
                     '"' => '"',
-                    _ => self.peek(),
+                    '\'' => '\'',
+                    _ => return Err(self.error_with_context(&format!("Invalid escape sequence '\\{}' in string literal", self.peek()))),
                 };
                 value.push(escaped);
                 self.advance();
@@ -303,6 +360,57 @@ impl<'a> Lexer<'a> {
         Ok(TokenType::StringLit(value))
     }
     
+    /// `r"` or `r#"` (with any number of `#`) starts a raw string.
+    fn at_raw_string(&self) -> bool {
+        let mut offset = 1;
+        while self.peek_ahead(offset) == '#' {
+            offset += 1;
+        }
+        self.peek_ahead(offset) == '"'
+    }
+
+    /// Reads `r"..."` or `r#"..."#`. Raw strings may span lines and have no escape
+    /// sequences; the `#`s let the contents include `"`, as in Rust.
+    fn read_raw_string(&mut self) -> Result<TokenType, String> {
+        let start_line = self.line;
+        let start_column = self.column;
+        self.advance();
+
+        let mut hashes = 0;
+        while self.peek() == '#' {
+            self.advance();
+            hashes += 1;
+        }
+        self.advance();
+
+        let mut value = String::new();
+        loop {
+            if self.is_at_end() {
+                self.line = start_line;
+                self.column = start_column;
+                return Err(self.error_with_context(&format!(
+                    "Unterminated raw string literal (missing closing '\"{}')",
+                    "#".repeat(hashes)
+                )));
+            }
+            if self.peek() == '"' && (1..=hashes).all(|offset| self.peek_ahead(offset) == '#') {
+                self.advance();
+                for _ in 0..hashes {
+                    self.advance();
+                }
+                break;
+            }
+            let ch = self.advance();
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            }
+            value.push(ch);
+        }
+
+        Ok(TokenType::StringLit(value))
+    }
+
     fn read_char(&mut self) -> Result<TokenType, String> {
         
         self.advance();
@@ -313,20 +421,25 @@ impl<'a> Lexer<'a> {
         
         let ch = if self.peek() == '\\' {
             self.advance();
-            match self.peek() {
-                'n' => '\n',
-                't' => '\t',
-                'r' => '\r',
-                '\\' => '\\',
-                '\'' => '\'',
-                _ => return Err(self.error_with_context(&format!("Invalid escape sequence '\\{}' in character literal", self.peek()))),
+            if self.peek() == 'x' {
+                self.read_hex_escape()?
+            } else {
+                let escaped = match self.peek() {
+                    'n' => '\n',
+                    't' => '\t',
+                    'r' => '\r',
+                    '0' => '\0',
+                    '\\' => '\\',
+                    '\'' => '\'',
+                    _ => return Err(self.error_with_context(&format!("Invalid escape sequence '\\{}' in character literal", self.peek()))),
+                };
+                self.advance();
+                escaped
             }
         } else {
-            self.peek()
+            self.advance()
         };
         
-        self.advance();
-        
         if self.peek() != '\'' {
             return Err(self.error_with_context("Unterminated character literal (expected closing quote)"));
         }
@@ -335,16 +448,139 @@ impl<'a> Lexer<'a> {
         Ok(TokenType::CharLit(ch))
     }
     
-    fn read_number(&mut self) -> TokenType {
-        let mut value = String::new();
+    /// Reads `xNN` after a backslash. Like Rust, the value must be ASCII (at most
+    /// `\x7F`) so that it always stands for a single byte.
+    fn read_hex_escape(&mut self) -> Result<char, String> {
+        self.advance();
+
+        let mut digits = String::new();
+        while digits.len() < 2 && !self.is_at_end() && self.peek().is_ascii_hexdigit() {
+            digits.push(self.advance());
+        }
+
+        if digits.len() < 2 {
+            return Err(self.error_with_context(&format!(
+                "Invalid hex escape '\\x{}': expected exactly two hex digits",
+                digits
+            )));
+        }
+
+        let value = u8::from_str_radix(&digits, 16).unwrap_or(0);
+        if value > 0x7F {
+            return Err(self.error_with_context(&format!(
+                "Hex escape '\\x{}' is out of range (must be at most \\x7F)",
+                digits
+            )));
+        }
+
+        Ok(value as char)
+    }
+
+    fn read_number(&mut self) -> Result<TokenType, String> {
+        if self.peek() == '0' {
+            match self.peek_ahead(1) {
+                'x' => return self.read_radix_number(16, "hex"),
+                'b' => return self.read_radix_number(2, "binary"),
+                'o' => return self.read_radix_number(8, "octal"),
+                _ => {}
+            }
+        }
+
+        let mut literal = String::new();
         
-        while !self.is_at_end() && self.peek().is_ascii_digit() {
-            value.push(self.advance());
+        // `_` separates digit groups (`1_000_000`) and is otherwise ignored
+        while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
+            literal.push(self.advance());
+        }
+        if literal.ends_with('_') {
+            return Err(self.error_with_context(&format!("Integer literal '{}' cannot end with '_'", literal)));
+        }
+
+        // A '.' only starts a fraction when a digit follows, so `0..10` stays a range
+        let has_fraction = self.peek() == '.' && self.peek_ahead(1).is_ascii_digit();
+        if has_fraction {
+            literal.push(self.advance());
+            while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
+                literal.push(self.advance());
+            }
+            if literal.ends_with('_') {
+                return Err(self.error_with_context(&format!("Float literal '{}' cannot end with '_'", literal)));
+            }
+        }
+
+        // `1.5e3`, `2e-4`: an exponent always makes the literal a float
+        let has_exponent = matches!(self.peek(), 'e' | 'E');
+        if has_exponent {
+            literal.push(self.advance());
+            if matches!(self.peek(), '+' | '-') {
+                literal.push(self.advance());
+            }
+            let digits_start = literal.len();
+            while !self.is_at_end() && (self.peek().is_ascii_digit() || self.peek() == '_') {
+                literal.push(self.advance());
+            }
+            if !literal[digits_start..].chars().any(|c| c.is_ascii_digit()) {
+                return Err(self.error_with_context(&format!("Float literal '{}' has no exponent digits", literal)));
+            }
+            if literal.ends_with('_') {
+                return Err(self.error_with_context(&format!("Float literal '{}' cannot end with '_'", literal)));
+            }
         }
+
+        if has_fraction || has_exponent {
+            let value: String = literal.chars().filter(|&c| c != '_').collect();
+            return value
+                .parse::<f64>()
+                .map(TokenType::Float)
+                .map_err(|_| self.error_with_context(&format!("Invalid float literal '{}'", literal)));
+        }
+        let value: String = literal.chars().filter(|&c| c != '_').collect();
         
-        TokenType::Number(value.parse().unwrap())
+        // Leading zeros are insignificant: `007` and `08` are decimal, never C-style octal.
+        // 9223372036854775808 only fits once negated; it is lexed as i64::MIN and the
+        // parser folds it into a preceding '-' (or rejects it when there is none).
+        match value.parse::<u64>() {
+            Ok(n) if n <= i64::MAX as u64 => Ok(TokenType::Number(n as i64)),
+            Ok(n) if n == i64::MIN.unsigned_abs() => Ok(TokenType::Number(i64::MIN)),
+            _ => Err(self.error_with_context(&format!("Integer literal '{}' is too large for type int", literal))),
+        }
     }
     
+    /// Reads a `0x`, `0b` or `0o` literal. Letters and digits are consumed up to the
+    /// next separator so that `0xG` or `0b102` is reported rather than split into tokens.
+    fn read_radix_number(&mut self, radix: u32, base_name: &str) -> Result<TokenType, String> {
+        let mut literal = String::new();
+        literal.push(self.advance());
+        literal.push(self.advance());
+
+        let digits_column = self.column;
+        let mut digits = String::new();
+        while !self.is_at_end() && (self.peek().is_ascii_alphanumeric() || self.peek() == '_') {
+            digits.push(self.advance());
+        }
+        literal.push_str(&digits);
+
+        if let Some(offset) = digits.find(|c: char| c != '_' && !c.is_digit(radix)) {
+            let invalid = digits[offset..].chars().next().unwrap_or_default();
+            self.column = digits_column + offset;
+            return Err(self.error_with_context(&format!(
+                "Invalid digit '{}' in {} literal '{}'",
+                invalid, base_name, literal
+            )));
+        }
+        let value: String = digits.chars().filter(|&c| c != '_').collect();
+        if value.is_empty() {
+            return Err(self.error_with_context(&format!("Expected {} digits after '{}'", base_name, &literal[..2])));
+        }
+        if digits.ends_with('_') {
+            return Err(self.error_with_context(&format!("Integer literal '{}' cannot end with '_'", literal)));
+        }
+
+        i64::from_str_radix(&value, radix)
+            .map(TokenType::Number)
+            .map_err(|_| self.error_with_context(&format!("Integer literal '{}' is too large for type int", literal)))
+    }
+
     fn read_identifier(&mut self) -> TokenType {
         let mut value = String::new();
         
@@ -372,9 +608,12 @@ impl<'a> Lexer<'a> {
             "return" => TokenType::Return,
             "break" => TokenType::Break,
             "continue" => TokenType::Continue,
+            "import" => TokenType::Import,
+            "alias" => TokenType::Alias,
             "true" => TokenType::True,
             "false" => TokenType::False,
             "int" => TokenType::IntType,
+            "float" => TokenType::FloatType,
             "bool" => TokenType::BoolType,
             "string" => TokenType::StringType,
             "char" => TokenType::CharType,
@@ -382,7 +621,7 @@ impl<'a> Lexer<'a> {
         }
     }
     
-    fn skip_whitespace_and_comments(&mut self) {
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
         while !self.is_at_end() {
             match self.peek() {
                 ' ' | '\t' | '\r' => {
@@ -394,13 +633,91 @@ impl<'a> Lexer<'a> {
                     self.column = 1;
                 }
                 '/' if self.peek_ahead(1) == '/' => {
+                    if self.at_module_doc() {
+                        break;
+                    }
+                    if self.peek_ahead(2) == '!' {
+                        self.warnings.push(format!(
+                            "{}:{}:{}: Warning: '//!' module docs must come before any code; this is treated as a plain comment",
+                            self.filename, self.line, self.column
+                        ));
+                    }
+                    // A trailing comment may end the file without a newline
                     while !self.is_at_end() && self.peek() != '\n' {
                         self.advance();
                     }
                 }
+                '/' if self.peek_ahead(1) == '*' => {
+                    self.skip_block_comment()?;
+                }
                 _ => break,
             }
         }
+        Ok(())
+    }
+
+    /// Skips a `/* ... */` comment. Block comments nest, so a region that already
+    /// contains one can be commented out as a whole.
+    fn skip_block_comment(&mut self) -> Result<(), String> {
+        let start_line = self.line;
+        let start_column = self.column;
+        self.advance();
+        self.advance();
+
+        let mut depth = 1;
+        while depth > 0 {
+            if self.is_at_end() {
+                // Point at the comment that was never closed, not at the end of the file
+                self.line = start_line;
+                self.column = start_column;
+                return Err(self.error_with_context("Unterminated block comment (missing closing '*/')"));
+            }
+            match (self.peek(), self.peek_ahead(1)) {
+                ('/', '*') => {
+                    self.advance();
+                    self.advance();
+                    depth += 1;
+                }
+                ('*', '/') => {
+                    self.advance();
+                    self.advance();
+                    depth -= 1;
+                }
+                ('\n', _) => {
+                    self.advance();
+                    self.line += 1;
+                    self.column = 1;
+                }
+                _ => {
+                    self.advance();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn at_module_doc(&self) -> bool {
+        self.at_module_start && self.peek() == '/' && self.peek_ahead(1) == '/' && self.peek_ahead(2) == '!'
+    }
+
+    fn read_module_doc(&mut self) -> Token {
+        let line = self.line;
+        let column = self.column;
+        for _ in 0..3 {
+            self.advance();
+        }
+
+        let mut text = String::new();
+        while !self.is_at_end() && self.peek() != '\n' {
+            text.push(self.advance());
+        }
+        let text = text.strip_prefix(' ').unwrap_or(&text).trim_end().to_string();
+
+        Token {
+            token_type: TokenType::ModuleDoc(text),
+            line,
+            column,
+        }
     }
 
     fn error_with_context(&self, message: &str) -> String {
@@ -429,15 +746,15 @@ impl<'a> Lexer<'a> {
         ));
 
         // Show previous line for context (if exists)
-        if self.line > 1 {
-            if let Some(prev_line) = lines.get(self.line - 2) {
-                error.push_str(&format!(
-                    "\x1b[1m\x1b[34m{:width$} |\x1b[0m {}\n",
-                    self.line - 1,
-                    prev_line,
-                    width = line_num_width
-                ));
-            }
+        if self.line > 1
+            && let Some(prev_line) = lines.get(self.line - 2)
+        {
+            error.push_str(&format!(
+                "\x1b[1m\x1b[34m{:width$} |\x1b[0m {}\n",
+                self.line - 1,
+                prev_line,
+                width = line_num_width
+            ));
         }
 
         // Current line with error
@@ -502,4 +819,104 @@ impl<'a> Lexer<'a> {
     fn is_at_end(&self) -> bool {
         self.current >= self.chars.len()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lex(source: &str) -> Vec<TokenType> {
+        Lexer::new(source, "test.brn")
+            .tokenize()
+            .unwrap()
+            .into_iter()
+            .map(|token| token.token_type)
+            .collect()
+    }
+
+    fn lex_error(source: &str) -> String {
+        Lexer::new(source, "test.brn").tokenize().unwrap_err()
+    }
+
+    #[test]
+    fn comment_at_end_of_file_without_newline() {
+        assert_eq!(lex("// final comment"), vec![TokenType::Eof]);
+        assert_eq!(lex("x // final comment"), vec![TokenType::Identifier("x".into()), TokenType::Eof]);
+        assert!(lex_error("\"abc\\").contains("Unterminated string literal"));
+    }
+
+    #[test]
+    fn leading_zeros_are_decimal() {
+        assert_eq!(lex("0 007 08"), vec![
+            TokenType::Number(0),
+            TokenType::Number(7),
+            TokenType::Number(8),
+            TokenType::Eof,
+        ]);
+    }
+
+    #[test]
+    fn radix_literals() {
+        assert_eq!(lex("0xff 0b101 0o17"), vec![
+            TokenType::Number(255),
+            TokenType::Number(5),
+            TokenType::Number(15),
+            TokenType::Eof,
+        ]);
+    }
+
+    #[test]
+    fn digit_separators() {
+        assert_eq!(lex("1_000_000 1_0"), vec![TokenType::Number(1_000_000), TokenType::Number(10), TokenType::Eof]);
+    }
+
+    #[test]
+    fn float_exponents_and_separators() {
+        assert_eq!(lex("1.5e3 2e-4 1_000.5"), vec![
+            TokenType::Float(1500.0),
+            TokenType::Float(2e-4),
+            TokenType::Float(1000.5),
+            TokenType::Eof,
+        ]);
+        assert!(lex_error("1.5e").contains("has no exponent digits"));
+    }
+
+    #[test]
+    fn hex_escapes() {
+        assert_eq!(lex(r#""\x41""#), vec![TokenType::StringLit("A".into()), TokenType::Eof]);
+        assert!(lex_error(r#""\xZZ""#).contains("expected exactly two hex digits"));
+    }
+
+    #[test]
+    fn nul_and_unknown_escapes() {
+        assert_eq!(lex(r#""a\0b""#), vec![TokenType::StringLit("a\0b".into()), TokenType::Eof]);
+        assert!(lex_error(r#""\q""#).contains("Invalid escape sequence '\\q'"));
+    }
+
+    #[test]
+    fn escaped_newline_joins_string_lines() {
+        assert_eq!(lex("\"foo\\\nbar\""), vec![TokenType::StringLit("foobar".into()), TokenType::Eof]);
+    }
+
+    #[test]
+    fn raw_strings_keep_backslashes() {
+        assert_eq!(lex(r##"r#"a\n"b"#"##), vec![TokenType::StringLit(r#"a\n"b"#.into()), TokenType::Eof]);
+    }
+
+    #[test]
+    fn nested_block_comments() {
+        assert_eq!(lex("/* a /* b */ c */ 1"), vec![TokenType::Number(1), TokenType::Eof]);
+        assert!(lex_error("/* a /* b */").contains("Unterminated block comment"));
+    }
+
+    #[test]
+    fn module_docs_only_at_start_of_file() {
+        let mut lexer = Lexer::new("//! one\n//! two\nfn main() {}\n//! late\n", "test.brn");
+        let tokens = lexer.tokenize().unwrap();
+        assert_eq!(tokens[0].token_type, TokenType::ModuleDoc("one".into()));
+        assert_eq!(tokens[1].token_type, TokenType::ModuleDoc("two".into()));
+        assert_eq!(tokens[2].token_type, TokenType::Fn);
+        assert_eq!(lexer.warnings().len(), 1);
+        assert!(lexer.warnings()[0].starts_with("test.brn:4:1: Warning"));
+    }
+}
@@ -0,0 +1,120 @@
+//! The Astral compiler as a library: the same lexer, parser, semantic analysis
+//! and LLVM IR generation the `astrallang` binary drives, for tools that want
+//! to compile programs without shelling out.
+
+use std::collections::HashSet;
+use std::fmt;
+
+pub mod lexer;
+pub mod parser;
+pub mod semantic;
+pub mod codegen;
+pub mod pretty;
+pub mod callgraph;
+mod format;
+
+use codegen::{CodeGenerator, CodegenOptions};
+use lexer::Lexer;
+use parser::{AstNode, Parser};
+use semantic::SemanticAnalyzer;
+
+/// Name used for the source in diagnostics from `compile_source`.
+pub const SOURCE_NAME: &str = "<source>";
+
+/// Why a program failed to compile. Each message is already formatted as a
+/// diagnostic, with the file name and position where there is one.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompileError {
+    Lex(String),
+    Parse(String),
+    /// Every error semantic analysis found, in source order.
+    Semantic(Vec<String>),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Lex(message) | CompileError::Parse(message) => write!(f, "{}", message),
+            CompileError::Semantic(errors) => write!(f, "{}", errors.join("\n")),
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// Lexes and parses `source`. Imports are left in the tree unresolved. Lexer
+/// warnings are appended to `warnings`, even when parsing fails.
+pub fn parse_source(source: &str, filename: &str, warnings: &mut Vec<String>) -> Result<AstNode, CompileError> {
+    let mut lexer = Lexer::new(source, filename);
+    let tokens = lexer.tokenize();
+    warnings.extend_from_slice(lexer.warnings());
+    Parser::new(tokens.map_err(CompileError::Lex)?, filename).parse().map_err(CompileError::Parse)
+}
+
+/// Runs semantic analysis over a parsed program whose entry function is `entry`.
+/// Warnings are appended to `warnings`, even when analysis fails.
+pub fn check_program(
+    program: &AstNode,
+    filename: &str,
+    entry: &str,
+    strict: bool,
+    warnings: &mut Vec<String>,
+) -> Result<(), CompileError> {
+    let mut analyzer = SemanticAnalyzer::new(filename, entry, strict);
+    let result = analyzer.analyze(program);
+    warnings.extend_from_slice(analyzer.warnings());
+    result.map_err(CompileError::Semantic)
+}
+
+/// Parses and checks a standalone program (no imports, no `--cfg` flags) and
+/// returns its AST. Warnings are discarded.
+pub fn compile_source(source: &str) -> Result<AstNode, CompileError> {
+    let mut warnings = Vec::new();
+    let program = parser::apply_cfg(parse_source(source, SOURCE_NAME, &mut warnings)?, &HashSet::new());
+    check_program(&program, SOURCE_NAME, "main", false, &mut warnings)?;
+    Ok(program)
+}
+
+/// Compiles a standalone program all the way to LLVM IR.
+pub fn compile_to_ir(source: &str, options: CodegenOptions) -> Result<String, CompileError> {
+    let program = compile_source(source)?;
+    Ok(CodeGenerator::new(options).generate(&program))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compile_source_returns_the_ast() {
+        let Ok(AstNode::Program(items)) = compile_source("fn main() {\nprintln(1);\n}\n") else {
+            panic!("expected a program");
+        };
+        assert!(matches!(&items[0], AstNode::FunctionDef { name, .. } if name == "main"));
+    }
+
+    #[test]
+    fn errors_are_tagged_by_stage() {
+        assert!(matches!(compile_source("fn main() { \"open }"), Err(CompileError::Lex(_))));
+        assert!(matches!(compile_source("fn main() {"), Err(CompileError::Parse(_))));
+        let Err(CompileError::Semantic(errors)) = compile_source("fn main() {\nprintln(x);\n}\n") else {
+            panic!("expected a semantic error");
+        };
+        assert!(errors[0].starts_with("<source>:2:"));
+    }
+
+    #[test]
+    fn warnings_are_reported_to_the_caller() {
+        let mut warnings = Vec::new();
+        let program = parse_source("fn main() {\nlet x = 1;\n}\n", SOURCE_NAME, &mut warnings).unwrap();
+        check_program(&program, SOURCE_NAME, "main", false, &mut warnings).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unused variable 'x'"));
+    }
+
+    #[test]
+    fn compile_to_ir_defines_main() {
+        let ir = compile_to_ir("fn main() {\nprintln(1);\n}\n", CodegenOptions::default()).unwrap();
+        assert!(ir.contains("define i32 @main()"));
+    }
+}
@@ -1,81 +1,291 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 use std::process;
 
-mod lexer;
-mod parser;
-mod semantic;
-mod codegen;
+use astrallang::{callgraph, parser, pretty, CompileError};
+use astrallang::parser::AstNode;
+use astrallang::codegen::{CodeGenerator, CodegenOptions};
 
-use lexer::Lexer;
-use parser::Parser;
-use semantic::SemanticAnalyzer;
-use codegen::CodeGenerator;
+struct Options {
+    cfg_flags: HashSet<String>,
+    test_mode: bool,
+    optimize: bool,
+    /// Skips ownership/type checking entirely. Unsafe: programs that would be
+    /// rejected are compiled anyway and may produce invalid IR or misbehave.
+    skip_semantic: bool,
+    /// Reject the implicit conversions semantic analysis otherwise lets through.
+    strict: bool,
+    /// Number of diagnostics printed before the rest are summarized.
+    max_errors: usize,
+    /// Function the compiled program starts in.
+    entry: String,
+    /// Read the source from standard input instead of a file.
+    stdin: bool,
+    /// Insert runtime bounds, null and overflow checks.
+    sanitize: bool,
+    /// Comment the generated IR with the source statement it came from.
+    annotate: bool,
+    /// Print the formatted source instead of compiling it.
+    fmt: bool,
+    /// Print which functions call which instead of compiling.
+    callgraph: bool,
+}
+
+/// Name used for the input file in diagnostics when reading from stdin.
+const STDIN_NAME: &str = "<stdin>";
+
+/// Every command-line flag the compiler understands, used to build `--help`.
+const FLAGS: &[(&str, &str)] = &[
+    ("--test", "Build a test runner that calls every `test_*` function instead of `main`"),
+    ("-O, --optimize", "Reuse variable loads within a basic block"),
+    ("--sanitize", "Insert runtime checks for array bounds, failed file reads and integer overflow"),
+    ("--annotate", "Comment the generated IR with the source line and statement it came from"),
+    ("--strict", "Reject implicit conversions: non-bool conditions and values that differ from a variable's type"),
+    ("--no-semantic", "Skip semantic analysis (unsafe: unchecked programs may miscompile)"),
+    ("--max-errors N", "Show at most N errors (default 20)"),
+    ("--entry NAME", "Start the program in function NAME instead of `main`"),
+    ("--fmt", "Print the input re-formatted in canonical style instead of compiling it"),
+    ("--callgraph", "Print which functions call which, and those never called, instead of compiling"),
+    ("--stdin", "Read the program from standard input (same as passing `-` as the input)"),
+    ("--cfg NAME", "Enable `if cfg(\"NAME\")` blocks; may be repeated"),
+    ("-h, --help", "Print this help and exit"),
+    ("-V, --version", "Print the compiler version and exit"),
+];
+
+fn usage(program: &str) -> String {
+    format!("Usage: {} [OPTIONS] <input.brn> [output]", program)
+}
+
+fn print_help(program: &str) {
+    println!("{}", usage(program));
+    println!();
+    println!("Compiles an Astral source file to LLVM IR (<output>.ll) and links it with clang.");
+    println!();
+    println!("Options:");
+    let width = FLAGS.iter().map(|(flag, _)| flag.len()).max().unwrap_or(0);
+    for (flag, description) in FLAGS {
+        println!("  {:width$}  {}", flag, description, width = width);
+    }
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
     
-    if args.len() < 2 {
-        eprintln!("Usage: {} <input.brn> [output]", args[0]);
+    let mut options = Options {
+        cfg_flags: HashSet::new(),
+        test_mode: false,
+        optimize: false,
+        skip_semantic: false,
+        strict: false,
+        max_errors: 20,
+        entry: "main".to_string(),
+        stdin: false,
+        sanitize: false,
+        annotate: false,
+        fmt: false,
+        callgraph: false,
+    };
+    let mut positional = Vec::new();
+    
+    let mut iter = args.iter().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--cfg" => match iter.next() {
+                Some(flag) => {
+                    options.cfg_flags.insert(flag.clone());
+                }
+                None => {
+                    eprintln!("Error: '--cfg' expects a flag name");
+                    process::exit(1);
+                }
+            },
+            "--max-errors" => match iter.next().map(|n| n.parse::<usize>()) {
+                Some(Ok(n)) if n > 0 => options.max_errors = n,
+                _ => {
+                    eprintln!("Error: '--max-errors' expects a positive number");
+                    process::exit(1);
+                }
+            },
+            "--entry" => match iter.next() {
+                Some(name) => options.entry = name.clone(),
+                None => {
+                    eprintln!("Error: '--entry' expects a function name");
+                    process::exit(1);
+                }
+            },
+            "--test" => options.test_mode = true,
+            "-O" | "--optimize" => options.optimize = true,
+            "--sanitize" => options.sanitize = true,
+            "--annotate" => options.annotate = true,
+            "--fmt" => options.fmt = true,
+            "--callgraph" => options.callgraph = true,
+            "--no-semantic" => options.skip_semantic = true,
+            "--strict" => options.strict = true,
+            "--stdin" | "-" => options.stdin = true,
+            "-h" | "--help" => {
+                print_help(&args[0]);
+                return;
+            }
+            "-V" | "--version" => {
+                println!("astrallang {}", env!("CARGO_PKG_VERSION"));
+                return;
+            }
+            flag if flag.starts_with('-') => {
+                eprintln!("Error: unknown option '{}'", flag);
+                eprintln!("Run '{} --help' for the list of options", args[0]);
+                process::exit(1);
+            }
+            _ => positional.push(arg.clone()),
+        }
+    }
+    
+    // With stdin as the input, the only positional argument is the output
+    if options.stdin {
+        if positional.len() > 1 {
+            eprintln!("Error: an input file cannot be given together with '--stdin'");
+            process::exit(1);
+        }
+        let output_file = positional.pop().unwrap_or_else(|| "out".to_string());
+        run(STDIN_NAME, &output_file, &options);
+        return;
+    }
+
+    if positional.is_empty() {
+        eprintln!("{}", usage(&args[0]));
         eprintln!("Example: {} main.brn", args[0]);
         process::exit(1);
     }
     
-    let input_file = &args[1];
-    let output_file = if args.len() > 2 {
-        args[2].clone()
+    let input_file = &positional[0];
+    let output_file = if positional.len() > 1 {
+        positional[1].clone()
     } else {
         input_file.trim_end_matches(".brn").to_string()
     };
     
-    compile_file(input_file, &output_file);
+    run(input_file, &output_file, &options);
 }
 
-fn compile_file(input_file: &str, output_file: &str) {
-    println!("Compiling {}...", input_file);
-    
-    // Read source file
-    let source = match fs::read_to_string(input_file) {
-        Ok(content) => content,
-        Err(e) => {
-            eprintln!("Error: Could not read file '{}': {}", input_file, e);
-            process::exit(1);
-        }
+fn run(input_file: &str, output_file: &str, options: &Options) {
+    if options.fmt {
+        format_file(input_file, options);
+    } else if options.callgraph {
+        print!("{}", callgraph::report(&load_program(input_file, options), &options.entry));
+    } else {
+        compile_file(input_file, output_file, options);
+    }
+}
+
+fn report_errors(errors: &[String], max_errors: usize) {
+    for error in errors.iter().take(max_errors) {
+        eprintln!("{}", error);
+    }
+    if errors.len() > max_errors {
+        let hidden = errors.len() - max_errors;
+        eprintln!("... and {} more error{}", hidden, if hidden == 1 { "" } else { "s" });
+    }
+}
+
+/// `--fmt`: parses the file on its own (imports are kept, not loaded) and
+/// prints it back in canonical style.
+fn format_file(input_file: &str, options: &Options) {
+    let source = if options.stdin {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source).map(|_| source)
+    } else {
+        fs::read_to_string(input_file)
     };
-    
-    // Step 1: Lexical Analysis
-    println!("  [1/4] Lexical analysis...");
-    let mut lexer = Lexer::new(&source, input_file);
-    let tokens = match lexer.tokenize() {
-        Ok(tokens) => tokens,
+    let source = source.unwrap_or_else(|e| {
+        eprintln!("Error: Could not read file '{}': {}", input_file, e);
+        process::exit(1);
+    });
+
+    let mut warnings = Vec::new();
+    let program = astrallang::parse_source(&source, input_file, &mut warnings);
+    for warning in &warnings {
+        eprintln!("{}", warning);
+    }
+    match program {
+        Ok(program) => print!("{}", pretty::pretty_print(&program)),
         Err(e) => {
             eprintln!("{}", e);
             process::exit(1);
         }
+    }
+}
+
+/// Reads the input with its imports spliced in and `--cfg` applied, exiting on errors.
+fn load_program(input_file: &str, options: &Options) -> AstNode {
+    let mut loader = ModuleLoader::new();
+    let loaded = if options.stdin {
+        loader.load_stdin()
+    } else {
+        loader.load(Path::new(input_file))
     };
-    
-    // Step 2: Parsing
-    println!("  [2/4] Parsing...");
-    let mut parser = Parser::new(tokens, input_file);
-    let ast = match parser.parse() {
-        Ok(ast) => ast,
+    for warning in &loader.warnings {
+        eprintln!("{}", warning);
+    }
+    match loaded {
+        Ok(nodes) => parser::apply_cfg(AstNode::Program(nodes), &options.cfg_flags),
         Err(e) => {
             eprintln!("{}", e);
             process::exit(1);
         }
-    };
+    }
+}
+
+fn compile_file(input_file: &str, output_file: &str, options: &Options) {
+    println!("Compiling {}...", input_file);
     
-    // Step 3: Semantic Analysis (Ownership & Memory Safety)
-    println!("  [3/4] Semantic analysis (ownership checking)...");
-    let mut analyzer = SemanticAnalyzer::new(input_file);
-    if let Err(e) = analyzer.analyze(&ast) {
-        eprintln!("{}", e);
+    // Step 1: Lexical Analysis & Parsing (including imported files)
+    println!("  [1/3] Lexing and parsing...");
+    let ast = load_program(input_file, options);
+
+    // A custom entry must exist, otherwise the program would silently have no `main`
+    if options.entry != "main"
+        && !options.test_mode
+        && let AstNode::Program(nodes) = &ast
+        && !nodes.iter().any(|node| matches!(node, AstNode::FunctionDef { name, .. } if *name == options.entry))
+    {
+        eprintln!("{}: Error: entry function '{}' is not defined", input_file, options.entry);
         process::exit(1);
     }
     
-    // Step 4: Code Generation
-    println!("  [4/4] Code generation...");
-    let mut codegen = CodeGenerator::new();
+    // Step 2: Semantic Analysis (Ownership & Memory Safety)
+    if options.skip_semantic {
+        println!("  [2/3] Semantic analysis skipped (--no-semantic: input is trusted, not checked)");
+    } else {
+        println!("  [2/3] Semantic analysis (ownership checking)...");
+        let mut warnings = Vec::new();
+        let result = astrallang::check_program(&ast, input_file, &options.entry, options.strict, &mut warnings);
+        for warning in &warnings {
+            eprintln!("{}", warning);
+        }
+        match result {
+            Ok(()) => {}
+            Err(CompileError::Semantic(errors)) => {
+                report_errors(&errors, options.max_errors);
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                process::exit(1);
+            }
+        }
+    }
+    
+    // Step 3: Code Generation
+    println!("  [3/3] Code generation...");
+    let mut codegen = CodeGenerator::new(CodegenOptions {
+        test_mode: options.test_mode,
+        optimize: options.optimize,
+        sanitize: options.sanitize,
+        annotate: options.annotate,
+        entry: Some(options.entry.clone()),
+    });
     let llvm_ir = codegen.generate(&ast);
     
     // Write LLVM IR to file
@@ -111,4 +321,115 @@ fn compile_file(input_file: &str, output_file: &str) {
             println!("You can compile manually with: clang {} -o {}", ll_file, output_file);
         }
     }
-}
\ No newline at end of file
+}
+
+/// Lexes and parses a source file, recursively splicing the top-level items of
+/// every `import "path";` into the program in place of the import statement.
+struct ModuleLoader {
+    /// Every file that has been (or is being) loaded, used to de-duplicate imports.
+    loaded: HashSet<PathBuf>,
+    /// The chain of files currently being loaded, used to detect import cycles.
+    loading: Vec<PathBuf>,
+    /// Top-level function/struct/enum names mapped to the file defining them.
+    definitions: HashMap<String, String>,
+    /// Lexer warnings from every loaded file.
+    warnings: Vec<String>,
+}
+
+impl ModuleLoader {
+    fn new() -> Self {
+        ModuleLoader {
+            loaded: HashSet::new(),
+            loading: Vec::new(),
+            definitions: HashMap::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn load(&mut self, path: &Path) -> Result<Vec<AstNode>, String> {
+        let filename = path.display().to_string();
+
+        let source = fs::read_to_string(path)
+            .map_err(|e| format!("Error: Could not read file '{}': {}", filename, e))?;
+        let canonical = fs::canonicalize(path)
+            .map_err(|e| format!("Error: Could not read file '{}': {}", filename, e))?;
+
+        self.loaded.insert(canonical.clone());
+        self.loading.push(canonical);
+
+        let program = self.load_source(&source, &filename, path.parent().unwrap_or(Path::new("")))?;
+
+        self.loading.pop();
+        Ok(program)
+    }
+
+    /// Loads a program piped on standard input. Its imports are resolved
+    /// relative to the current directory.
+    fn load_stdin(&mut self) -> Result<Vec<AstNode>, String> {
+        let mut source = String::new();
+        io::stdin()
+            .read_to_string(&mut source)
+            .map_err(|e| format!("Error: Could not read {}: {}", STDIN_NAME, e))?;
+        self.load_source(&source, STDIN_NAME, Path::new(""))
+    }
+
+    fn load_source(&mut self, source: &str, filename: &str, dir: &Path) -> Result<Vec<AstNode>, String> {
+        let filename = filename.to_string();
+
+        let parsed = astrallang::parse_source(source, &filename, &mut self.warnings);
+        let AstNode::Program(nodes) = parsed.map_err(|e| e.to_string())? else {
+            unreachable!("parser always produces a program");
+        };
+
+        let mut program = Vec::new();
+
+        for node in nodes {
+            if let AstNode::Import { path: import_path, location } = &node {
+                let resolved = dir.join(import_path);
+                let canonical = fs::canonicalize(&resolved).map_err(|e| format!(
+                    "{}:{}:{}: Error: cannot import '{}': {}",
+                    filename, location.line, location.column, import_path, e
+                ))?;
+
+                if self.loading.contains(&canonical) {
+                    return Err(format!(
+                        "{}:{}:{}: Error: cyclic import of '{}'",
+                        filename, location.line, location.column, import_path
+                    ));
+                }
+
+                // Only the root file's `//!` docs describe the program
+                if !self.loaded.contains(&canonical) {
+                    let imported = self.load(&resolved)?;
+                    program.extend(imported.into_iter().filter(|node| !matches!(node, AstNode::ModuleDoc(_))));
+                }
+                continue;
+            }
+
+            if let Some(name) = definition_name(&node) {
+                if let Some(defined_in) = self.definitions.get(name)
+                    && *defined_in != filename
+                {
+                    return Err(format!(
+                        "{}: Error: '{}' is already defined in '{}'",
+                        filename, name, defined_in
+                    ));
+                }
+                self.definitions.insert(name.to_string(), filename.clone());
+            }
+
+            program.push(node);
+        }
+
+        Ok(program)
+    }
+}
+
+fn definition_name(node: &AstNode) -> Option<&str> {
+    match node {
+        AstNode::FunctionDef { name, .. }
+        | AstNode::StructDef { name, .. }
+        | AstNode::EnumDef { name, .. } => Some(name),
+        _ => None,
+    }
+}
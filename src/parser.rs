@@ -1,4 +1,5 @@
 use crate::lexer::{Token, TokenType};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Copy)]
 pub struct Location {
@@ -9,6 +10,20 @@ pub struct Location {
 #[derive(Debug, Clone)]
 pub enum AstNode {
     Program(Vec<AstNode>),
+    /// The `//!` lines at the top of a file, one entry per line; always the first program item.
+    ModuleDoc(Vec<String>),
+
+    Import {
+        path: String,
+        location: Location,
+    },
+
+    /// `alias C = Color;` — the parser substitutes the target wherever the alias is used.
+    Alias {
+        name: String,
+        target: String,
+        location: Location,
+    },
 
     LetBinding {
         mutable: bool,
@@ -17,6 +32,12 @@ pub enum AstNode {
         value: Box<AstNode>,
         location: Location,
     },
+    /// `let Enum::Variant(x) = value;` — only allowed when the pattern cannot fail.
+    LetPattern {
+        pattern: Pattern,
+        value: Box<AstNode>,
+        location: Location,
+    },
     Assignment {
         name: String,
         value: Box<AstNode>,
@@ -28,6 +49,10 @@ pub enum AstNode {
         params: Vec<Parameter>,
         return_type: Option<String>,
         body: Box<AstNode>,
+        /// Set by `@deprecated` / `@deprecated("note")`; the note may be empty.
+        deprecated: Option<String>,
+        /// Position of the `fn` keyword.
+        location: Location,
     },
 
     StructDef {
@@ -76,6 +101,7 @@ pub enum AstNode {
         operand: Box<AstNode>,
     },
     Number(i64),
+    Float(f64),
     Boolean(bool),
     Character(char),
     StringLit(String),
@@ -83,10 +109,18 @@ pub enum AstNode {
         name: String,
         location: Location,
     },
-    Reference(Box<AstNode>),
+    /// `&value` or `&mut value`
+    Reference {
+        value: Box<AstNode>,
+        mutable: bool,
+    },
+    SizeOf(String),
+    /// `expr?` on a `Result`: unwraps `Ok`, or returns the `Err` value from the enclosing function.
+    Try(Box<AstNode>),
     Call {
         name: String,
         args: Vec<AstNode>,
+        location: Location,
     },
     MethodCall {
         object: Box<AstNode>,
@@ -103,34 +137,81 @@ pub enum AstNode {
         then_block: Box<AstNode>,
         else_block: Option<Box<AstNode>>,
     },
+    /// `if cond { a } else { b }` used as a value; each branch is a single expression.
+    IfExpr {
+        condition: Box<AstNode>,
+        then_value: Box<AstNode>,
+        else_value: Box<AstNode>,
+    },
+    /// `if let Enum::Variant(x) = value { ... }`: runs the then-block with `x` bound
+    /// when `value` is that variant, otherwise the else-block.
+    IfLet {
+        pattern: Pattern,
+        value: Box<AstNode>,
+        then_block: Box<AstNode>,
+        else_block: Option<Box<AstNode>>,
+    },
     While {
         condition: Box<AstNode>,
         body: Box<AstNode>,
+        /// Runs once if the condition is false on the first check, i.e. the body never ran.
+        else_block: Option<Box<AstNode>>,
     },
     For {
         variable: String,
         iterator: Box<AstNode>,
         body: Box<AstNode>,
     },
+    /// `start..end` in a `for` head: the integers from `start` up to, not including, `end`.
+    Range {
+        start: Box<AstNode>,
+        end: Box<AstNode>,
+    },
     Match {
         value: Box<AstNode>,
         arms: Vec<MatchArm>,
     },
     Return(Option<Box<AstNode>>),
-    Break,
-    Continue,
+    Break {
+        location: Location,
+    },
+    Continue {
+        location: Location,
+    },
 
     Block(Vec<AstNode>),
     ExpressionStatement(Box<AstNode>),
 }
 
+impl AstNode {
+    /// Source position of the node, for the variants that record one.
+    pub fn location(&self) -> Option<Location> {
+        match self {
+            AstNode::Import { location, .. }
+            | AstNode::Alias { location, .. }
+            | AstNode::FunctionDef { location, .. }
+            | AstNode::LetBinding { location, .. }
+            | AstNode::LetPattern { location, .. }
+            | AstNode::Assignment { location, .. }
+            | AstNode::ArrayAssignment { location, .. }
+            | AstNode::Identifier { location, .. }
+            | AstNode::Call { location, .. }
+            | AstNode::Break { location }
+            | AstNode::Continue { location } => Some(*location),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum BinOp {
     Add,
     Sub,
     Mul,
     Div,
+    /// Truncated remainder (`srem`); see the `mod` builtin for floored modulo.
     Mod,
+    Pow,
     Equal,
     NotEqual,
     LessThan,
@@ -153,6 +234,8 @@ pub struct Parameter {
     pub is_mutable: bool,
     pub name: String,
     pub param_type: String,
+    /// `name: T = value`; callers may leave out trailing parameters that have one.
+    pub default_value: Option<AstNode>,
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +257,7 @@ pub struct MatchArm {
 }
 
 #[derive(Debug, Clone)]
+#[allow(clippy::enum_variant_names)]
 pub enum Pattern {
     Identifier(String),
     EnumPattern {
@@ -181,13 +265,110 @@ pub enum Pattern {
         variant: String,
         binding: Option<String>,
     },
+    StringLiteral(String),
     Wildcard,
 }
 
+/// Resolves `if cfg("name") { ... } else { ... }` at compile time, keeping only the
+/// branch selected by the `--cfg` flags. Unknown flags evaluate to false.
+pub fn apply_cfg(node: AstNode, flags: &HashSet<String>) -> AstNode {
+    match node {
+        AstNode::Program(nodes) => {
+            AstNode::Program(nodes.into_iter().map(|n| apply_cfg(n, flags)).collect())
+        }
+        AstNode::FunctionDef { name, params, return_type, body, deprecated, location } => AstNode::FunctionDef {
+            name,
+            params,
+            return_type,
+            body: Box::new(apply_cfg(*body, flags)),
+            deprecated,
+            location,
+        },
+        AstNode::Block(statements) => {
+            AstNode::Block(statements.into_iter().map(|n| apply_cfg(n, flags)).collect())
+        }
+        AstNode::If { condition, then_block, else_block } => match cfg_condition(&condition, flags) {
+            Some(true) => apply_cfg(*then_block, flags),
+            Some(false) => match else_block {
+                Some(else_block) => apply_cfg(*else_block, flags),
+                None => AstNode::Block(Vec::new()),
+            },
+            None => AstNode::If {
+                condition,
+                then_block: Box::new(apply_cfg(*then_block, flags)),
+                else_block: else_block.map(|b| Box::new(apply_cfg(*b, flags))),
+            },
+        },
+        AstNode::IfLet { pattern, value, then_block, else_block } => AstNode::IfLet {
+            pattern,
+            value,
+            then_block: Box::new(apply_cfg(*then_block, flags)),
+            else_block: else_block.map(|b| Box::new(apply_cfg(*b, flags))),
+        },
+        AstNode::While { condition, body, else_block } => AstNode::While {
+            condition,
+            body: Box::new(apply_cfg(*body, flags)),
+            else_block: else_block.map(|b| Box::new(apply_cfg(*b, flags))),
+        },
+        AstNode::For { variable, iterator, body } => AstNode::For {
+            variable,
+            iterator,
+            body: Box::new(apply_cfg(*body, flags)),
+        },
+        AstNode::Match { value, arms } => AstNode::Match {
+            value,
+            arms: arms
+                .into_iter()
+                .map(|arm| MatchArm { pattern: arm.pattern, body: apply_cfg(arm.body, flags) })
+                .collect(),
+        },
+        AstNode::IfExpr { condition, then_value, else_value } => match cfg_condition(&condition, flags) {
+            Some(true) => apply_cfg(*then_value, flags),
+            Some(false) => apply_cfg(*else_value, flags),
+            None => AstNode::IfExpr {
+                condition,
+                then_value: Box::new(apply_cfg(*then_value, flags)),
+                else_value: Box::new(apply_cfg(*else_value, flags)),
+            },
+        },
+        // Values are searched for `if cfg(...)` expressions
+        AstNode::LetBinding { mutable, name, type_annotation, value, location } => AstNode::LetBinding {
+            mutable,
+            name,
+            type_annotation,
+            value: Box::new(apply_cfg(*value, flags)),
+            location,
+        },
+        AstNode::Assignment { name, value, location } => AstNode::Assignment {
+            name,
+            value: Box::new(apply_cfg(*value, flags)),
+            location,
+        },
+        AstNode::Return(value) => AstNode::Return(value.map(|v| Box::new(apply_cfg(*v, flags)))),
+        AstNode::ExpressionStatement(expr) => AstNode::ExpressionStatement(Box::new(apply_cfg(*expr, flags))),
+        other => other,
+    }
+}
+
+fn cfg_condition(condition: &AstNode, flags: &HashSet<String>) -> Option<bool> {
+    if let AstNode::Call { name, args, .. } = condition
+        && name == "cfg"
+        && let [AstNode::StringLit(flag)] = args.as_slice()
+    {
+        return Some(flags.contains(flag));
+    }
+    None
+}
+
 pub struct Parser<'a> {
     tokens: Vec<Token>,
     current: usize,
     filename: &'a str,
+    /// Cleared while parsing `if`/`while`/`for`/`match` heads so that in
+    /// `match value { ... }` the brace opens the body rather than a struct literal.
+    allow_struct_literal: bool,
+    /// Type aliases declared so far in this file
+    aliases: HashMap<String, String>,
 }
 
 impl<'a> Parser<'a> {
@@ -196,19 +377,34 @@ impl<'a> Parser<'a> {
             tokens,
             current: 0,
             filename,
+            allow_struct_literal: true,
+            aliases: HashMap::new(),
         }
     }
 
     pub fn parse(&mut self) -> Result<AstNode, String> {
         let mut nodes = Vec::new();
 
+        let mut doc = Vec::new();
+        while let TokenType::ModuleDoc(line) = &self.peek().token_type {
+            doc.push(line.clone());
+            self.advance();
+        }
+        if !doc.is_empty() {
+            nodes.push(AstNode::ModuleDoc(doc));
+        }
+
         while !self.is_at_end() {
-            if self.check(&TokenType::Fn) {
+            if self.check(&TokenType::Fn) || self.check(&TokenType::At) {
                 nodes.push(self.parse_function()?);
             } else if self.check(&TokenType::Struct) {
                 nodes.push(self.parse_struct_def()?);
             } else if self.check(&TokenType::Enum) {
                 nodes.push(self.parse_enum_def()?);
+            } else if self.check(&TokenType::Import) {
+                nodes.push(self.parse_import()?);
+            } else if self.check(&TokenType::Alias) {
+                nodes.push(self.parse_alias()?);
             } else {
                 nodes.push(self.parse_statement()?);
             }
@@ -217,7 +413,60 @@ impl<'a> Parser<'a> {
         Ok(AstNode::Program(nodes))
     }
 
+    fn parse_import(&mut self) -> Result<AstNode, String> {
+        let location = Location {
+            line: self.peek().line,
+            column: self.peek().column,
+        };
+
+        self.consume(&TokenType::Import, "Expected 'import'")?;
+
+        let path = if let TokenType::StringLit(path) = &self.peek().token_type {
+            let path = path.clone();
+            self.advance();
+            path
+        } else {
+            return Err(self.error("Expected file path string after 'import'"));
+        };
+
+        self.consume(&TokenType::Semicolon, "Expected ';'")?;
+
+        Ok(AstNode::Import { path, location })
+    }
+
+    fn parse_alias(&mut self) -> Result<AstNode, String> {
+        let location = Location {
+            line: self.peek().line,
+            column: self.peek().column,
+        };
+
+        self.consume(&TokenType::Alias, "Expected 'alias'")?;
+        let name = self.consume_identifier("Expected alias name")?;
+        self.consume(&TokenType::Assign, "Expected '=' after alias name")?;
+        let target = self.parse_type()?;
+        self.consume(&TokenType::Semicolon, "Expected ';'")?;
+
+        if let Some(existing) = self.aliases.get(&name) {
+            return Err(format!(
+                "{}:{}:{}: alias '{}' is already defined (as '{}')",
+                self.filename, location.line, location.column, name, existing
+            ));
+        }
+        self.aliases.insert(name.clone(), target.clone());
+
+        Ok(AstNode::Alias { name, target, location })
+    }
+
+    fn resolve_alias(&self, name: String) -> String {
+        self.aliases.get(&name).cloned().unwrap_or(name)
+    }
+
     fn parse_function(&mut self) -> Result<AstNode, String> {
+        let deprecated = self.parse_attributes()?;
+        let location = Location {
+            line: self.peek().line,
+            column: self.peek().column,
+        };
         self.consume(&TokenType::Fn, "Expected 'fn'")?;
 
         let name = self.consume_identifier("Expected function name")?;
@@ -240,9 +489,41 @@ impl<'a> Parser<'a> {
             params,
             return_type,
             body,
+            deprecated,
+            location,
         })
     }
 
+    /// Parses the `@name` / `@name("arg")` attributes in front of a function.
+    /// `@deprecated` is the only one; its note (or "") is returned.
+    fn parse_attributes(&mut self) -> Result<Option<String>, String> {
+        let mut deprecated = None;
+
+        while self.check(&TokenType::At) {
+            self.advance();
+            if let TokenType::Identifier(name) = &self.peek().token_type
+                && name != "deprecated"
+            {
+                return Err(self.error(&format!("Unknown attribute '@{}'", name)));
+            }
+            self.consume_identifier("Expected attribute name after '@'")?;
+
+            let mut note = String::new();
+            if self.check(&TokenType::LParen) {
+                self.advance();
+                let TokenType::StringLit(text) = &self.peek().token_type else {
+                    return Err(self.error("Expected a string literal in '@deprecated(...)'"));
+                };
+                note = text.clone();
+                self.advance();
+                self.consume(&TokenType::RParen, "Expected ')'")?;
+            }
+            deprecated = Some(note);
+        }
+
+        Ok(deprecated)
+    }
+
     fn parse_parameters(&mut self) -> Result<Vec<Parameter>, String> {
         let mut params = Vec::new();
 
@@ -269,11 +550,28 @@ impl<'a> Parser<'a> {
             self.consume(&TokenType::Colon, "Expected ':'")?;
             let param_type = self.parse_type()?;
 
+            let default_value = if self.check(&TokenType::Assign) {
+                if is_reference {
+                    return Err(self.error(&format!("Reference parameter '{}' cannot have a default value", name)));
+                }
+                self.advance();
+                Some(self.parse_expression()?)
+            } else {
+                if params.iter().any(|p: &Parameter| p.default_value.is_some()) {
+                    return Err(self.error(&format!(
+                        "Parameter '{}' needs a default value because an earlier parameter has one",
+                        name
+                    )));
+                }
+                None
+            };
+
             params.push(Parameter {
                 is_reference,
                 is_mutable,
                 name,
                 param_type,
+                default_value,
             });
 
             if !self.check(&TokenType::Comma) {
@@ -349,6 +647,10 @@ impl<'a> Parser<'a> {
                 self.advance();
                 Ok("int".to_string())
             }
+            TokenType::FloatType => {
+                self.advance();
+                Ok("float".to_string())
+            }
             TokenType::BoolType => {
                 self.advance();
                 Ok("bool".to_string())
@@ -364,6 +666,13 @@ impl<'a> Parser<'a> {
             TokenType::LBracket => {
                 self.advance();
                 let elem_type = self.parse_type()?;
+
+                // `[T]` is an array whose length is only known at runtime (e.g. `main`'s args)
+                if self.check(&TokenType::RBracket) {
+                    self.advance();
+                    return Ok(format!("[{}]", elem_type));
+                }
+
                 self.consume(&TokenType::Semicolon, "Expected ';'")?;
 
                 let size = if let TokenType::Number(n) = self.peek().token_type {
@@ -380,7 +689,7 @@ impl<'a> Parser<'a> {
             TokenType::Identifier(name) => {
                 let name = name.clone();
                 self.advance();
-                Ok(name)
+                Ok(self.resolve_alias(name))
             }
             _ => Err(self.error("Expected type")),
         }
@@ -402,7 +711,7 @@ impl<'a> Parser<'a> {
             if self.check(&TokenType::Assign) {
                 self.advance();
                 let value = Box::new(self.parse_expression()?);
-                self.consume(&TokenType::Semicolon, "Expected ';'")?;
+                self.end_statement()?;
 
                 return Ok(AstNode::ArrayAssignment {
                     array: name,
@@ -411,7 +720,7 @@ impl<'a> Parser<'a> {
                     location,
                 });
             } else {
-                self.consume(&TokenType::Semicolon, "Expected ';'")?;
+                self.end_statement()?;
                 return Ok(AstNode::ExpressionStatement(Box::new(
                     AstNode::Index {
                         array: Box::new(AstNode::Identifier { name, location }),
@@ -425,7 +734,10 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_statement(&mut self) -> Result<AstNode, String> {
-        if self.check(&TokenType::Let) {
+        if self.check(&TokenType::Fn) || self.check(&TokenType::At) {
+            // Nested function, visible only inside the enclosing block
+            self.parse_function()
+        } else if self.check(&TokenType::Let) {
             self.parse_let_binding()
         } else if self.check(&TokenType::If) {
             self.parse_if()
@@ -438,13 +750,21 @@ impl<'a> Parser<'a> {
         } else if self.check(&TokenType::Return) {
             self.parse_return()
         } else if self.check(&TokenType::Break) {
+            let location = Location {
+                line: self.peek().line,
+                column: self.peek().column,
+            };
             self.advance();
-            self.consume(&TokenType::Semicolon, "Expected ';'")?;
-            Ok(AstNode::Break)
+            self.end_statement()?;
+            Ok(AstNode::Break { location })
         } else if self.check(&TokenType::Continue) {
+            let location = Location {
+                line: self.peek().line,
+                column: self.peek().column,
+            };
             self.advance();
-            self.consume(&TokenType::Semicolon, "Expected ';'")?;
-            Ok(AstNode::Continue)
+            self.end_statement()?;
+            Ok(AstNode::Continue { location })
         } else if self.check(&TokenType::LBrace) {
             self.parse_block()
         } else if self.check_identifier() {
@@ -455,16 +775,28 @@ impl<'a> Parser<'a> {
                 self.parse_array_assignment_or_expression()
             } else {
                 let expr = self.parse_expression()?;
-                self.consume(&TokenType::Semicolon, "Expected ';'")?;
+                self.end_statement()?;
                 Ok(AstNode::ExpressionStatement(Box::new(expr)))
             }
         } else {
             let expr = self.parse_expression()?;
-            self.consume(&TokenType::Semicolon, "Expected ';'")?;
+            self.end_statement()?;
             Ok(AstNode::ExpressionStatement(Box::new(expr)))
         }
     }
 
+    /// Statements end with `;`, which may be left off the last statement of a block.
+    fn end_statement(&mut self) -> Result<(), String> {
+        if self.check(&TokenType::Semicolon) {
+            self.advance();
+            Ok(())
+        } else if self.check(&TokenType::RBrace) {
+            Ok(())
+        } else {
+            Err(self.error("Expected ';'"))
+        }
+    }
+
     fn parse_let_binding(&mut self) -> Result<AstNode, String> {
         let location = Location {
             line: self.peek().line,
@@ -480,6 +812,20 @@ impl<'a> Parser<'a> {
             false
         };
 
+        if self.check_identifier()
+            && self.peek_ahead(1).token_type == TokenType::Colon
+            && self.peek_ahead(2).token_type == TokenType::Colon
+        {
+            if mutable {
+                return Err(self.error("A destructuring 'let' cannot be 'mut'"));
+            }
+            let pattern = self.parse_pattern()?;
+            self.consume(&TokenType::Assign, "Expected '='")?;
+            let value = Box::new(self.parse_expression()?);
+            self.end_statement()?;
+            return Ok(AstNode::LetPattern { pattern, value, location });
+        }
+
         let name = self.consume_identifier("Expected variable name")?;
 
         let type_annotation = if self.check(&TokenType::Colon) {
@@ -491,7 +837,7 @@ impl<'a> Parser<'a> {
 
         self.consume(&TokenType::Assign, "Expected '='")?;
         let value = Box::new(self.parse_expression()?);
-        self.consume(&TokenType::Semicolon, "Expected ';'")?;
+        self.end_statement()?;
 
         Ok(AstNode::LetBinding {
             mutable,
@@ -511,7 +857,7 @@ impl<'a> Parser<'a> {
         let name = self.consume_identifier("Expected variable name")?;
         self.consume(&TokenType::Assign, "Expected '='")?;
         let value = Box::new(self.parse_expression()?);
-        self.consume(&TokenType::Semicolon, "Expected ';'")?;
+        self.end_statement()?;
 
         Ok(AstNode::Assignment { name, value, location })
     }
@@ -530,7 +876,17 @@ impl<'a> Parser<'a> {
 
     fn parse_if(&mut self) -> Result<AstNode, String> {
         self.consume(&TokenType::If, "Expected 'if'")?;
-        let condition = Box::new(self.parse_expression()?);
+
+        let pattern = if self.check(&TokenType::Let) {
+            self.advance();
+            let pattern = self.parse_pattern()?;
+            self.consume(&TokenType::Assign, "Expected '=' after 'if let' pattern")?;
+            Some(pattern)
+        } else {
+            None
+        };
+
+        let condition = Box::new(self.parse_condition()?);
         let then_block = Box::new(self.parse_block()?);
 
         let else_block = if self.check(&TokenType::Else) {
@@ -544,6 +900,15 @@ impl<'a> Parser<'a> {
             None
         };
 
+        if let Some(pattern) = pattern {
+            return Ok(AstNode::IfLet {
+                pattern,
+                value: condition,
+                then_block,
+                else_block,
+            });
+        }
+
         Ok(AstNode::If {
             condition,
             then_block,
@@ -551,19 +916,58 @@ impl<'a> Parser<'a> {
         })
     }
 
+    /// `if cond { a } else { b }` in expression position. Unlike the statement form,
+    /// the `else` is required and each branch holds exactly one expression.
+    fn parse_if_expression(&mut self) -> Result<AstNode, String> {
+        self.consume(&TokenType::If, "Expected 'if'")?;
+        let condition = Box::new(self.parse_condition()?);
+        let then_value = Box::new(self.parse_branch_value()?);
+
+        if !self.check(&TokenType::Else) {
+            return Err(self.error("An 'if' used as a value needs an 'else' branch"));
+        }
+        self.advance();
+        let else_value = Box::new(if self.check(&TokenType::If) {
+            self.parse_if_expression()?
+        } else {
+            self.parse_branch_value()?
+        });
+
+        Ok(AstNode::IfExpr { condition, then_value, else_value })
+    }
+
+    fn parse_branch_value(&mut self) -> Result<AstNode, String> {
+        self.consume(&TokenType::LBrace, "Expected '{'")?;
+        let value = self.parse_expression()?;
+        self.consume(&TokenType::RBrace, "Expected '}': each branch of an 'if' value is a single expression")?;
+        Ok(value)
+    }
+
     fn parse_while(&mut self) -> Result<AstNode, String> {
         self.consume(&TokenType::While, "Expected 'while'")?;
-        let condition = Box::new(self.parse_expression()?);
+        let condition = Box::new(self.parse_condition()?);
         let body = Box::new(self.parse_block()?);
 
-        Ok(AstNode::While { condition, body })
+        let else_block = if self.check(&TokenType::Else) {
+            self.advance();
+            Some(Box::new(self.parse_block()?))
+        } else {
+            None
+        };
+
+        Ok(AstNode::While { condition, body, else_block })
     }
 
     fn parse_for(&mut self) -> Result<AstNode, String> {
         self.consume(&TokenType::For, "Expected 'for'")?;
         let variable = self.consume_identifier("Expected loop variable")?;
         self.consume(&TokenType::In, "Expected 'in'")?;
-        let iterator = Box::new(self.parse_expression()?);
+        let mut iterator = Box::new(self.parse_condition()?);
+        if self.check(&TokenType::DotDot) {
+            self.advance();
+            let end = Box::new(self.parse_condition()?);
+            iterator = Box::new(AstNode::Range { start: iterator, end });
+        }
         let body = Box::new(self.parse_block()?);
 
         Ok(AstNode::For {
@@ -575,7 +979,7 @@ impl<'a> Parser<'a> {
 
     fn parse_match(&mut self) -> Result<AstNode, String> {
         self.consume(&TokenType::Match, "Expected 'match'")?;
-        let value = Box::new(self.parse_expression()?);
+        let value = Box::new(self.parse_condition()?);
 
         self.consume(&TokenType::LBrace, "Expected '{'")?;
         let mut arms = Vec::new();
@@ -583,7 +987,12 @@ impl<'a> Parser<'a> {
         while !self.check(&TokenType::RBrace) && !self.is_at_end() {
             let pattern = self.parse_pattern()?;
             self.consume(&TokenType::FatArrow, "Expected '=>'")?;
-            let body = self.parse_expression()?;
+            // A block body can hold statements, including `break`/`continue` of an enclosing loop
+            let body = if self.check(&TokenType::LBrace) {
+                self.parse_block()?
+            } else {
+                self.parse_expression()?
+            };
 
             arms.push(MatchArm { pattern, body });
 
@@ -616,7 +1025,7 @@ impl<'a> Parser<'a> {
                 };
 
                 Ok(Pattern::EnumPattern {
-                    enum_name: first,
+                    enum_name: self.resolve_alias(first),
                     variant,
                     binding,
                 })
@@ -626,29 +1035,47 @@ impl<'a> Parser<'a> {
         } else if self.check(&TokenType::Identifier("_".to_string())) {
             self.advance();
             Ok(Pattern::Wildcard)
+        } else if let TokenType::StringLit(value) = &self.peek().token_type {
+            let value = value.clone();
+            self.advance();
+            Ok(Pattern::StringLiteral(value))
         } else {
             Err(self.error("Expected pattern"))
         }
     }
 
+    fn parse_condition(&mut self) -> Result<AstNode, String> {
+        let previous = self.allow_struct_literal;
+        self.allow_struct_literal = false;
+        let condition = self.parse_expression();
+        self.allow_struct_literal = previous;
+        condition
+    }
+
     fn parse_return(&mut self) -> Result<AstNode, String> {
         self.consume(&TokenType::Return, "Expected 'return'")?;
 
-        let value = if self.check(&TokenType::Semicolon) {
+        let value = if self.check(&TokenType::Semicolon) || self.check(&TokenType::RBrace) {
             None
         } else {
             Some(Box::new(self.parse_expression()?))
         };
 
-        self.consume(&TokenType::Semicolon, "Expected ';'")?;
+        self.end_statement()?;
         Ok(AstNode::Return(value))
     }
 
     fn parse_expression(&mut self) -> Result<AstNode, String> {
         if self.check(&TokenType::Ampersand) {
             self.advance();
+            let mutable = if self.check(&TokenType::Mut) {
+                self.advance();
+                true
+            } else {
+                false
+            };
             let expr = self.parse_or()?;
-            return Ok(AstNode::Reference(Box::new(expr)));
+            return Ok(AstNode::Reference { value: Box::new(expr), mutable });
         }
 
         self.parse_or()
@@ -761,7 +1188,7 @@ impl<'a> Parser<'a> {
     }
 
     fn parse_term(&mut self) -> Result<AstNode, String> {
-        let mut left = self.parse_factor()?;
+        let mut left = self.parse_unary()?;
 
         while self.check(&TokenType::Star) || self.check(&TokenType::Slash) || self.check(&TokenType::Percent) {
             let op = if self.check(&TokenType::Star) {
@@ -775,7 +1202,7 @@ impl<'a> Parser<'a> {
                 BinOp::Mod
             };
 
-            let right = self.parse_factor()?;
+            let right = self.parse_unary()?;
             left = AstNode::BinaryOp {
                 op,
                 left: Box::new(left),
@@ -786,13 +1213,78 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
+    fn parse_unary(&mut self) -> Result<AstNode, String> {
+        if self.check(&TokenType::Minus) {
+            self.advance();
+
+            // Fold '-' into a following literal so that i64::MIN can be written. Not
+            // done for `-2 ** 2`, which negates the power: -(2 ** 2) == -4.
+            if self.peek_ahead(1).token_type != TokenType::StarStar {
+                match self.peek().token_type {
+                    TokenType::Number(n) => {
+                        self.advance();
+                        return Ok(AstNode::Number(n.wrapping_neg()));
+                    }
+                    TokenType::Float(f) => {
+                        self.advance();
+                        return Ok(AstNode::Float(-f));
+                    }
+                    _ => {}
+                }
+            }
+
+            let operand = self.parse_unary()?;
+            return Ok(AstNode::UnaryOp {
+                op: UnOp::Negate,
+                operand: Box::new(operand),
+            });
+        }
+
+        if self.check(&TokenType::Not) {
+            self.advance();
+            let operand = self.parse_unary()?;
+            return Ok(AstNode::UnaryOp {
+                op: UnOp::Not,
+                operand: Box::new(operand),
+            });
+        }
+
+        self.parse_power()
+    }
+
+    fn parse_power(&mut self) -> Result<AstNode, String> {
+        let base = self.parse_factor()?;
+
+        if self.check(&TokenType::StarStar) {
+            self.advance();
+            // Right-associative, and the exponent may itself be negated: 2 ** -1
+            let exponent = self.parse_unary()?;
+            return Ok(AstNode::BinaryOp {
+                op: BinOp::Pow,
+                left: Box::new(base),
+                right: Box::new(exponent),
+            });
+        }
+
+        Ok(base)
+    }
+
     fn parse_factor(&mut self) -> Result<AstNode, String> {
         match &self.peek().token_type {
             TokenType::Number(n) => {
                 let n = *n;
+                if n == i64::MIN {
+                    return Err(self.error("Integer literal '9223372036854775808' is too large for type int"));
+                }
                 self.advance();
                 Ok(AstNode::Number(n))
             }
+            TokenType::Float(f) => {
+                let f = *f;
+                self.advance();
+                Ok(AstNode::Float(f))
+            }
+            TokenType::If => self.parse_if_expression(),
             TokenType::True => {
                 self.advance();
                 Ok(AstNode::Boolean(true))
@@ -809,7 +1301,7 @@ impl<'a> Parser<'a> {
             TokenType::StringLit(s) => {
                 let s = s.clone();
                 self.advance();
-                Ok(AstNode::StringLit(s))
+                self.parse_postfix(AstNode::StringLit(s))
             }
             TokenType::LBracket => {
                 self.advance();
@@ -825,7 +1317,14 @@ impl<'a> Parser<'a> {
                 }
 
                 self.consume(&TokenType::RBracket, "Expected ']'")?;
-                Ok(AstNode::ArrayLit(elements))
+                self.parse_postfix(AstNode::ArrayLit(elements))
+            }
+            TokenType::Identifier(name) if name == "size_of" && self.peek_ahead(1).token_type == TokenType::LParen => {
+                self.advance();
+                self.advance();
+                let type_name = self.parse_type()?;
+                self.consume(&TokenType::RParen, "Expected ')'")?;
+                Ok(AstNode::SizeOf(type_name))
             }
             TokenType::Identifier(name) => {
                 let name = name.clone();
@@ -839,9 +1338,12 @@ impl<'a> Parser<'a> {
             }
             TokenType::LParen => {
                 self.advance();
-                let expr = self.parse_expression()?;
+                let previous = self.allow_struct_literal;
+                self.allow_struct_literal = true;
+                let expr = self.parse_expression();
+                self.allow_struct_literal = previous;
                 self.consume(&TokenType::RParen, "Expected ')'")?;
-                Ok(expr)
+                Ok(expr?)
             }
             _ => Err(self.error("Expected expression")),
         }
@@ -854,8 +1356,8 @@ impl<'a> Parser<'a> {
                 let args = self.parse_arguments()?;
                 self.consume(&TokenType::RParen, "Expected ')'")?;
 
-                if let AstNode::Identifier { name, .. } = left {
-                    left = AstNode::Call { name, args };
+                if let AstNode::Identifier { name, location } = left {
+                    left = AstNode::Call { name, args, location };
                 } else {
                     return Err(self.error("Invalid function call"));
                 }
@@ -886,12 +1388,15 @@ impl<'a> Parser<'a> {
                     array: Box::new(left),
                     index: Box::new(index),
                 };
-            } else if self.check(&TokenType::LBrace) {
+            } else if self.check(&TokenType::Question) {
+                self.advance();
+                left = AstNode::Try(Box::new(left));
+            } else if self.check(&TokenType::LBrace) && self.allow_struct_literal {
                 if let AstNode::Identifier { name, .. } = left {
                     self.advance();
                     let fields = self.parse_field_inits()?;
                     self.consume(&TokenType::RBrace, "Expected '}'")?;
-                    left = AstNode::StructInit { name, fields };
+                    left = AstNode::StructInit { name: self.resolve_alias(name), fields };
                 } else {
                     break;
                 }
@@ -911,7 +1416,7 @@ impl<'a> Parser<'a> {
                     };
 
                     left = AstNode::EnumValue {
-                        enum_name,
+                        enum_name: self.resolve_alias(enum_name),
                         variant,
                         value,
                     };
@@ -934,13 +1439,7 @@ impl<'a> Parser<'a> {
         }
 
         loop {
-            if self.check(&TokenType::Ampersand) {
-                self.advance();
-                let expr = self.parse_expression()?;
-                args.push(AstNode::Reference(Box::new(expr)));
-            } else {
-                args.push(self.parse_expression()?);
-            }
+            args.push(self.parse_expression()?);
 
             if !self.check(&TokenType::Comma) {
                 break;
@@ -1042,4 +1541,103 @@ impl<'a> Parser<'a> {
             self.filename, token.line, token.column, message
         )
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    fn parse(source: &str) -> AstNode {
+        let tokens = Lexer::new(source, "test.brn").tokenize().unwrap();
+        Parser::new(tokens, "test.brn").parse().unwrap()
+    }
+
+    /// Parses `source` as the value of a `let` and renders it as an s-expression.
+    fn expr(source: &str) -> String {
+        let AstNode::Program(items) = parse(&format!("fn main() {{ let v = {}; }}", source)) else {
+            unreachable!();
+        };
+        let AstNode::FunctionDef { body, .. } = &items[0] else {
+            panic!("expected a function");
+        };
+        let AstNode::Block(statements) = body.as_ref() else {
+            panic!("expected a block");
+        };
+        let AstNode::LetBinding { value, .. } = &statements[0] else {
+            panic!("expected a let");
+        };
+        sexpr(value)
+    }
+
+    fn sexpr(node: &AstNode) -> String {
+        match node {
+            AstNode::BinaryOp { op, left, right } => format!("({:?} {} {})", op, sexpr(left), sexpr(right)),
+            AstNode::UnaryOp { op, operand } => format!("({:?} {})", op, sexpr(operand)),
+            AstNode::Number(n) => n.to_string(),
+            AstNode::Identifier { name, .. } => name.clone(),
+            other => panic!("unexpected node {:?}", other),
+        }
+    }
+
+    /// The program after `cfg` pruning with `flags` set, as its debug rendering.
+    fn configured(source: &str, flags: &[&str]) -> String {
+        let flags = flags.iter().map(|flag| flag.to_string()).collect();
+        format!("{:?}", apply_cfg(parse(source), &flags))
+    }
+
+    #[test]
+    fn remainder_binds_like_multiplication() {
+        assert_eq!(expr("10 % 3 * 2"), "(Mul (Mod 10 3) 2)");
+        assert_eq!(expr("10 * 3 % 2"), "(Mod (Mul 10 3) 2)");
+        assert_eq!(expr("1 + 10 % 3"), "(Add 1 (Mod 10 3))");
+    }
+
+    #[test]
+    fn unary_minus_binds_looser_than_power() {
+        assert_eq!(expr("-2 ** 2"), "(Negate (Pow 2 2))");
+        assert_eq!(expr("(-2) ** 2"), "(Pow -2 2)");
+        assert_eq!(expr("2 ** 3 ** 2"), "(Pow 2 (Pow 3 2))");
+    }
+
+    #[test]
+    fn comparison_is_looser_than_additive() {
+        assert_eq!(expr("a + b < c - d"), "(LessThan (Add a b) (Sub c d))");
+    }
+
+    #[test]
+    fn most_negative_literal() {
+        assert_eq!(expr("-9223372036854775808"), i64::MIN.to_string());
+    }
+
+    #[test]
+    fn last_semicolon_is_optional() {
+        let with = format!("{:?}", parse("fn main() {\nlet x = 1;\nprint(x);\n}\n"));
+        let without = format!("{:?}", parse("fn main() {\nlet x = 1;\nprint(x)\n}\n"));
+        assert_eq!(with, without);
+    }
+
+    #[test]
+    fn cfg_blocks_follow_flags() {
+        let source = "fn main() { if cfg(\"debug\") { print(1); } print(2); }";
+        assert!(configured(source, &["debug"]).contains("Number(1)"));
+        assert!(!configured(source, &[]).contains("Number(1)"));
+        assert!(configured(source, &[]).contains("Number(2)"));
+    }
+
+    #[test]
+    fn cfg_inside_match_arms_and_let_values() {
+        let source = "enum E { A, B }\n\
+            fn main() {\n\
+                let e = E::A;\n\
+                match e { E::A => { if cfg(\"debug\") { println(1); } } E::B => {} }\n\
+                let v = if cfg(\"debug\") { 10 } else { 20 };\n\
+            }";
+        let debug = configured(source, &["debug"]);
+        assert!(debug.contains("Number(1)"));
+        assert!(debug.contains("Number(10)") && !debug.contains("Number(20)"));
+        let release = configured(source, &[]);
+        assert!(!release.contains("Number(1)"));
+        assert!(release.contains("Number(20)") && !release.contains("Number(10)"));
+    }
+}
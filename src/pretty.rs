@@ -0,0 +1,400 @@
+use crate::parser::{AstNode, BinOp, EnumVariant, Field, MatchArm, Parameter, Pattern, UnOp};
+
+const INDENT: &str = "    ";
+
+/// Re-emits a parsed program as canonically formatted source for `--fmt`:
+/// four-space indentation, one statement per line, single spaces around binary
+/// operators and only the parentheses that precedence requires. Comments other
+/// than leading `//!` docs are not part of the AST and are dropped.
+pub fn pretty_print(program: &AstNode) -> String {
+    let mut printer = Printer { output: String::new(), depth: 0 };
+    match program {
+        AstNode::Program(items) => printer.items(items),
+        other => printer.statement(other),
+    }
+    printer.output
+}
+
+struct Printer {
+    output: String,
+    depth: usize,
+}
+
+impl Printer {
+    fn line(&mut self, text: &str) {
+        if !text.is_empty() {
+            for _ in 0..self.depth {
+                self.output.push_str(INDENT);
+            }
+            self.output.push_str(text);
+        }
+        self.output.push('\n');
+    }
+
+    /// Top-level items, with a blank line around definitions but not between
+    /// consecutive imports, aliases or statements.
+    fn items(&mut self, items: &[AstNode]) {
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 && (is_definition(item) || is_definition(&items[i - 1]) || is_doc(&items[i - 1])) {
+                self.line("");
+            }
+            self.statement(item);
+        }
+    }
+
+    fn statement(&mut self, node: &AstNode) {
+        match node {
+            AstNode::ModuleDoc(lines) => {
+                for text in lines {
+                    if text.is_empty() {
+                        self.line("//!");
+                    } else {
+                        self.line(&format!("//! {}", text));
+                    }
+                }
+            }
+            AstNode::Import { path, .. } => self.line(&format!("import {};", string_literal(path))),
+            AstNode::Alias { name, target, .. } => self.line(&format!("alias {} = {};", name, target)),
+            AstNode::FunctionDef { name, params, return_type, body, deprecated, .. } => {
+                match deprecated.as_deref() {
+                    Some("") => self.line("@deprecated"),
+                    Some(note) => self.line(&format!("@deprecated({})", string_literal(note))),
+                    None => {}
+                }
+                let params: Vec<String> = params.iter().map(parameter).collect();
+                let returns = match return_type {
+                    Some(return_type) => format!(" -> {}", return_type),
+                    None => String::new(),
+                };
+                self.block(&format!("fn {}({}){}", name, params.join(", "), returns), body);
+            }
+            AstNode::StructDef { name, fields } => self.struct_def(name, fields),
+            AstNode::EnumDef { name, variants } => self.enum_def(name, variants),
+            AstNode::LetBinding { mutable, name, type_annotation, value, .. } => {
+                let annotation = match type_annotation {
+                    Some(ty) => format!(": {}", ty),
+                    None => String::new(),
+                };
+                let keyword = if *mutable { "let mut" } else { "let" };
+                self.line(&format!("{} {}{} = {};", keyword, name, annotation, expression(value)));
+            }
+            AstNode::LetPattern { pattern: let_pattern, value, .. } => {
+                self.line(&format!("let {} = {};", pattern(let_pattern), expression(value)));
+            }
+            AstNode::Assignment { name, value, .. } => {
+                self.line(&format!("{} = {};", name, expression(value)));
+            }
+            AstNode::ArrayAssignment { array, index, value, .. } => {
+                self.line(&format!("{}[{}] = {};", array, expression(index), expression(value)));
+            }
+            AstNode::If { .. } | AstNode::IfLet { .. } => self.if_chain(node, "if"),
+            AstNode::While { condition, body, else_block } => {
+                self.block_open(&format!("while {}", expression(condition)), body);
+                match else_block {
+                    Some(else_block) => self.block("} else", else_block),
+                    None => self.line("}"),
+                }
+            }
+            AstNode::For { variable, iterator, body } => {
+                self.block(&format!("for {} in {}", variable, expression(iterator)), body);
+            }
+            AstNode::Match { value, arms } => self.match_expr(value, arms),
+            AstNode::Return(Some(value)) => self.line(&format!("return {};", expression(value))),
+            AstNode::Return(None) => self.line("return;"),
+            AstNode::Break { .. } => self.line("break;"),
+            AstNode::Continue { .. } => self.line("continue;"),
+            AstNode::Block(_) => {
+                self.line("{");
+                self.block_body(node);
+                self.line("}");
+            }
+            AstNode::ExpressionStatement(expr) => self.line(&format!("{};", expression(expr))),
+            other => self.line(&format!("{};", expression(other))),
+        }
+    }
+
+    /// `header {`, the body, and the closing `}`.
+    fn block(&mut self, header: &str, body: &AstNode) {
+        self.block_open(header, body);
+        self.line("}");
+    }
+
+    fn block_open(&mut self, header: &str, body: &AstNode) {
+        self.line(&format!("{} {{", header));
+        self.block_body(body);
+    }
+
+    fn block_body(&mut self, body: &AstNode) {
+        self.depth += 1;
+        match body {
+            AstNode::Block(statements) => {
+                for statement in statements {
+                    self.statement(statement);
+                }
+            }
+            other => self.statement(other),
+        }
+        self.depth -= 1;
+    }
+
+    /// `if` / `} else if` / `} else` chains, closing each block on the next header's line.
+    fn if_chain(&mut self, node: &AstNode, keyword: &str) {
+        let (head, then_block, else_block) = match node {
+            AstNode::If { condition, then_block, else_block } => (expression(condition), then_block, else_block),
+            AstNode::IfLet { pattern: if_pattern, value, then_block, else_block } => {
+                (format!("let {} = {}", pattern(if_pattern), expression(value)), then_block, else_block)
+            }
+            _ => return,
+        };
+        self.block_open(&format!("{} {}", keyword, head), then_block);
+        match else_block.as_deref() {
+            Some(nested @ (AstNode::If { .. } | AstNode::IfLet { .. })) => self.if_chain(nested, "} else if"),
+            Some(else_block) => self.block("} else", else_block),
+            None => self.line("}"),
+        }
+    }
+
+    fn match_expr(&mut self, value: &AstNode, arms: &[MatchArm]) {
+        self.line(&format!("match {} {{", expression(value)));
+        self.depth += 1;
+        for arm in arms {
+            let pattern = pattern(&arm.pattern);
+            if let AstNode::Block(_) = arm.body {
+                self.block(&format!("{} =>", pattern), &arm.body);
+            } else {
+                self.line(&format!("{} => {},", pattern, expression(&arm.body)));
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn struct_def(&mut self, name: &str, fields: &[Field]) {
+        self.line(&format!("struct {} {{", name));
+        self.depth += 1;
+        for field in fields {
+            self.line(&format!("{}: {};", field.name, field.field_type));
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+
+    fn enum_def(&mut self, name: &str, variants: &[EnumVariant]) {
+        self.line(&format!("enum {} {{", name));
+        self.depth += 1;
+        for variant in variants {
+            match &variant.value_type {
+                Some(value_type) => self.line(&format!("{}({}),", variant.name, value_type)),
+                None => self.line(&format!("{},", variant.name)),
+            }
+        }
+        self.depth -= 1;
+        self.line("}");
+    }
+}
+
+fn is_definition(node: &AstNode) -> bool {
+    matches!(node, AstNode::FunctionDef { .. } | AstNode::StructDef { .. } | AstNode::EnumDef { .. })
+}
+
+fn is_doc(node: &AstNode) -> bool {
+    matches!(node, AstNode::ModuleDoc(_))
+}
+
+fn parameter(param: &Parameter) -> String {
+    let reference = if param.is_reference { "&" } else { "" };
+    let mutable = if param.is_mutable { "mut " } else { "" };
+    let default = match &param.default_value {
+        Some(value) => format!(" = {}", expression(value)),
+        None => String::new(),
+    };
+    format!("{}{}{}: {}{}", reference, mutable, param.name, param.param_type, default)
+}
+
+fn pattern(pattern: &Pattern) -> String {
+    match pattern {
+        Pattern::Identifier(name) => name.clone(),
+        Pattern::EnumPattern { enum_name, variant, binding: Some(binding) } => {
+            format!("{}::{}({})", enum_name, variant, binding)
+        }
+        Pattern::EnumPattern { enum_name, variant, binding: None } => format!("{}::{}", enum_name, variant),
+        Pattern::StringLiteral(value) => string_literal(value),
+        Pattern::Wildcard => "_".to_string(),
+    }
+}
+
+/// Binding strength of an expression, matching the parser's precedence levels
+/// from `parse_or` (lowest) down to `parse_factor` and postfix operators (highest).
+fn precedence(node: &AstNode) -> u8 {
+    match node {
+        AstNode::BinaryOp { op, .. } => binary_precedence(op),
+        AstNode::UnaryOp { .. } => 6,
+        AstNode::Number(n) if *n < 0 => 6,
+        AstNode::Float(f) if *f < 0.0 => 6,
+        AstNode::Reference { .. } => 0,
+        _ => 8,
+    }
+}
+
+fn binary_precedence(op: &BinOp) -> u8 {
+    match op {
+        BinOp::Or => 1,
+        BinOp::And => 2,
+        BinOp::Equal
+        | BinOp::NotEqual
+        | BinOp::LessThan
+        | BinOp::LessEqual
+        | BinOp::GreaterThan
+        | BinOp::GreaterEqual => 3,
+        BinOp::Add | BinOp::Sub => 4,
+        BinOp::Mul | BinOp::Div | BinOp::Mod => 5,
+        BinOp::Pow => 7,
+    }
+}
+
+fn binary_symbol(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Mod => "%",
+        BinOp::Pow => "**",
+        BinOp::Equal => "==",
+        BinOp::NotEqual => "!=",
+        BinOp::LessThan => "<",
+        BinOp::LessEqual => "<=",
+        BinOp::GreaterThan => ">",
+        BinOp::GreaterEqual => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+/// `node` printed where an operand of at least `min` precedence is expected.
+fn operand(node: &AstNode, min: u8) -> String {
+    if precedence(node) < min {
+        format!("({})", expression(node))
+    } else {
+        expression(node)
+    }
+}
+
+fn list(nodes: &[AstNode]) -> String {
+    nodes.iter().map(expression).collect::<Vec<_>>().join(", ")
+}
+
+fn expression(node: &AstNode) -> String {
+    match node {
+        AstNode::Number(n) => n.to_string(),
+        AstNode::Float(f) => float_literal(*f),
+        AstNode::Boolean(b) => b.to_string(),
+        AstNode::Character(c) => char_literal(*c),
+        AstNode::StringLit(value) => string_literal(value),
+        AstNode::Identifier { name, .. } => name.clone(),
+        AstNode::BinaryOp { op: BinOp::Pow, left, right } => {
+            // Right-associative: the base is a factor, the exponent a unary expression
+            format!("{} ** {}", operand(left, 8), operand(right, 6))
+        }
+        AstNode::BinaryOp { op, left, right } => {
+            let level = binary_precedence(op);
+            format!("{} {} {}", operand(left, level), binary_symbol(op), operand(right, level + 1))
+        }
+        AstNode::UnaryOp { op, operand: inner } => {
+            let symbol = match op {
+                UnOp::Not => "!",
+                UnOp::Negate => "-",
+            };
+            format!("{}{}", symbol, operand(inner, 6))
+        }
+        AstNode::Reference { value, mutable } => {
+            format!("&{}{}", if *mutable { "mut " } else { "" }, expression(value))
+        }
+        AstNode::Call { name, args, .. } => format!("{}({})", name, list(args)),
+        AstNode::MethodCall { object, method, args } => {
+            format!("{}.{}({})", operand(object, 8), method, list(args))
+        }
+        AstNode::MemberAccess { object, field } => format!("{}.{}", operand(object, 8), field),
+        AstNode::Index { array, index } => format!("{}[{}]", operand(array, 8), expression(index)),
+        AstNode::Try(inner) => format!("{}?", operand(inner, 8)),
+        AstNode::IfExpr { condition, then_value, else_value } => {
+            let else_part = match else_value.as_ref() {
+                nested @ AstNode::IfExpr { .. } => expression(nested),
+                other => format!("{{ {} }}", expression(other)),
+            };
+            format!("if {} {{ {} }} else {}", expression(condition), expression(then_value), else_part)
+        }
+        AstNode::ArrayLit(elements) => format!("[{}]", list(elements)),
+        AstNode::Range { start, end } => format!("{}..{}", expression(start), expression(end)),
+        AstNode::SizeOf(type_name) => format!("size_of({})", type_name),
+        AstNode::EnumValue { enum_name, variant, value: Some(value) } => {
+            format!("{}::{}({})", enum_name, variant, expression(value))
+        }
+        AstNode::EnumValue { enum_name, variant, value: None } => format!("{}::{}", enum_name, variant),
+        AstNode::StructInit { name, fields } => {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|(field, value)| format!("{}: {}", field, expression(value)))
+                .collect();
+            format!("{} {{ {} }}", name, fields.join(", "))
+        }
+        AstNode::ArrayType { element_type, size } => format!("[{}; {}]", element_type, size),
+        _ => String::new(),
+    }
+}
+
+fn escape(c: char, quote: char) -> String {
+    match c {
+        '\n' => "\\n".to_string(),
+        '\t' => "\\t".to_string(),
+        '\r' => "\\r".to_string(),
+        '\\' => "\\\\".to_string(),
+        c if c == quote => format!("\\{}", c),
+        c if c.is_ascii_control() => format!("\\x{:02X}", c as u32),
+        c => c.to_string(),
+    }
+}
+
+fn string_literal(value: &str) -> String {
+    let escaped: String = value.chars().map(|c| escape(c, '"')).collect();
+    format!("\"{}\"", escaped)
+}
+
+/// `f64`'s `Display` never uses an exponent but drops the fraction of whole
+/// numbers, which would turn `2.0` back into an int.
+fn float_literal(f: f64) -> String {
+    let text = f.to_string();
+    if text.contains('.') { text } else { format!("{}.0", text) }
+}
+
+fn char_literal(c: char) -> String {
+    format!("'{}'", escape(c, '\''))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn format(source: &str) -> String {
+        let tokens = Lexer::new(source, "test.brn").tokenize().unwrap();
+        pretty_print(&Parser::new(tokens, "test.brn").parse().unwrap())
+    }
+
+    #[test]
+    fn canonical_style_is_idempotent() {
+        let messy = "fn  add( a:int,b :int ) -> int {return a+b*2 ;}\nfn main(){let x=add( 1,2 );\n  println( x );}\n";
+        let expected = "fn add(a: int, b: int) -> int {\n    return a + b * 2;\n}\n\nfn main() {\n    let x = add(1, 2);\n    println(x);\n}\n";
+        assert_eq!(format(messy), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn parentheses_kept_only_where_needed() {
+        let formatted = format("fn main() { let x = (1 + 2) * 3; let y = 1 + (2 * 3); let z = (10 % 3) * 2; }");
+        assert!(formatted.contains("let x = (1 + 2) * 3;"));
+        assert!(formatted.contains("let y = 1 + 2 * 3;"));
+        assert!(formatted.contains("let z = 10 % 3 * 2;"));
+    }
+}
@@ -1,77 +1,195 @@
-use crate::parser::{AstNode, BinOp, Parameter, Location};
-use std::collections::HashMap;
+use crate::format::{parse_format_string, placeholder_count, FormatPiece};
+use crate::parser::{AstNode, BinOp, EnumVariant, Location, Parameter, Pattern, UnOp};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone)]
 struct VarInfo {
     is_consumed: bool,
     borrow_count: usize,
     is_mutable: bool,
+    is_loop_variable: bool,
     declared_line: usize,
     declared_column: usize,
     var_type: String,
+    /// Read, borrowed or moved somewhere; unused variables get a warning when their scope ends
+    was_used: bool,
 }
 
 pub struct SemanticAnalyzer<'a> {
     filename: &'a str,
+    /// Name of the program's entry function (`main` unless configured otherwise)
+    entry: &'a str,
     symbol_table: Vec<HashMap<String, VarInfo>>,
     current_line: usize,
     current_column: usize,
     in_loop: bool,
+    user_types: HashSet<String>,
+    /// Declared return type of the function being analyzed; `None` means it returns nothing
+    return_type: Option<String>,
+    /// Parameters of each top-level function
+    functions: HashMap<String, Vec<Parameter>>,
+    /// Nested functions declared in each open scope, parallel to `symbol_table`
+    local_functions: Vec<HashSet<String>>,
+    /// Top-level functions marked `@deprecated`, with their (possibly empty) note
+    deprecated: HashMap<String, String>,
+    /// Diagnostics that do not stop compilation
+    warnings: Vec<String>,
+    /// Variants of each top-level enum, for checking destructuring patterns
+    enum_variants: HashMap<String, Vec<EnumVariant>>,
+    /// `--strict`: conditions must be bools, and values must match the declared or
+    /// existing type of the variable they are stored in
+    strict: bool,
 }
 
 impl<'a> SemanticAnalyzer<'a> {
-    pub fn new(filename: &'a str) -> Self {
+    pub fn new(filename: &'a str, entry: &'a str, strict: bool) -> Self {
         SemanticAnalyzer {
             filename,
+            entry,
             symbol_table: vec![HashMap::new()],
             current_line: 1,
             current_column: 1,
             in_loop: false,
+            // `Option` is built in; see `CodeGenerator::builtin_enums`
+            user_types: HashSet::from(["Option".to_string()]),
+            return_type: None,
+            functions: HashMap::new(),
+            local_functions: vec![HashSet::new()],
+            deprecated: HashMap::new(),
+            warnings: Vec::new(),
+            enum_variants: HashMap::from([(
+                "Option".to_string(),
+                vec![
+                    EnumVariant { name: "Some".to_string(), value_type: Some("int".to_string()) },
+                    EnumVariant { name: "None".to_string(), value_type: None },
+                ],
+            )]),
+            strict,
         }
     }
 
+    /// Warnings found by the last `analyze`, in source order.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
     fn is_copy_type(&self, name: &str) -> bool {
         if let Some(info) = self.lookup_variable(name) {
-            matches!(info.var_type.as_str(), "int" | "bool" | "char")
+            matches!(info.var_type.as_str(), "int" | "float" | "bool" | "char")
         } else {
             false
         }
     }
 
-    pub fn analyze(&mut self, ast: &AstNode) -> Result<(), String> {
-        self.visit(ast)
+    /// Analyzes a program, collecting one error per failing top-level item so that
+    /// problems in independent functions are all reported in a single run.
+    pub fn analyze(&mut self, ast: &AstNode) -> Result<(), Vec<String>> {
+        let AstNode::Program(nodes) = ast else {
+            return self.visit(ast).map_err(|e| vec![e]);
+        };
+
+        self.collect_declarations(nodes);
+
+        let mut errors = Vec::new();
+        for node in nodes {
+            let saved_scopes = self.symbol_table.clone();
+            let saved_functions = self.local_functions.clone();
+
+            if let Err(e) = self.visit(node) {
+                errors.push(e);
+                self.symbol_table = saved_scopes;
+                self.local_functions = saved_functions;
+                self.return_type = None;
+                self.in_loop = false;
+            }
+        }
+
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn collect_declarations(&mut self, nodes: &[AstNode]) {
+        for node in nodes {
+            match node {
+                AstNode::StructDef { name, .. } => {
+                    self.user_types.insert(name.clone());
+                }
+                AstNode::EnumDef { name, variants } => {
+                    self.user_types.insert(name.clone());
+                    self.enum_variants.insert(name.clone(), variants.clone());
+                }
+                AstNode::FunctionDef { name, params, deprecated, .. } => {
+                    self.functions.insert(name.clone(), params.clone());
+                    if let Some(note) = deprecated {
+                        self.deprecated.insert(name.clone(), note.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
     }
 
     fn visit(&mut self, node: &AstNode) -> Result<(), String> {
         match node {
             AstNode::Program(nodes) => {
+                self.collect_declarations(nodes);
                 for node in nodes {
                     self.visit(node)?;
                 }
                 Ok(())
             }
 
-            AstNode::FunctionDef { params, body, .. } => {
+            AstNode::FunctionDef { name, params, return_type, body, location, .. } => {
+                self.current_line = location.line;
+                self.current_column = location.column;
+
+                if name == self.entry
+                    && !(params.is_empty() || (params.len() == 1 && params[0].param_type == "[string]"))
+                {
+                    return Err(format!(
+                        "{}:{}:{}: Error: entry function '{}' must take no parameters or a single 'args: [string]' parameter",
+                        self.filename, location.line, location.column, name
+                    ));
+                }
+
+                // A function body cannot see the locals of an enclosing function
+                let outer_scopes = std::mem::replace(&mut self.symbol_table, vec![HashMap::new()]);
+                let outer_return_type =
+                    std::mem::replace(&mut self.return_type, effective_return_type(return_type, params, body));
+                let outer_in_loop = std::mem::replace(&mut self.in_loop, false);
+
+                self.check_default_values(params)?;
                 self.push_scope();
 
+                // Parameters are part of the signature, so an unused one is not reported
                 for param in params {
-                    let is_mutable = if param.is_reference {
-                        param.is_mutable
-                    } else {
-                        param.is_mutable
-                    };
-
                     self.declare_variable(
                         &param.name,
-                        is_mutable,
+                        param.is_mutable,
                         param.param_type.clone(),
                         0,
                         0,
                     );
+                    self.mark_used(&param.name);
                 }
 
                 self.visit(body)?;
                 self.pop_scope();
+
+                // The entry function may fall off the end; the program then exits with 0
+                if let Some(return_type) = return_type
+                    && return_type != "void"
+                    && name != self.entry
+                    && !always_returns(body)
+                {
+                    return Err(format!(
+                        "{}:{}:{}: Error: missing return in function '{}'\nNote: '{}' is declared to return '{}' but can reach the end of its body",
+                        self.filename, location.line, location.column, name, name, return_type
+                    ));
+                }
+
+                self.symbol_table = outer_scopes;
+                self.return_type = outer_return_type;
+                self.in_loop = outer_in_loop;
                 Ok(())
             }
 
@@ -79,6 +197,17 @@ impl<'a> SemanticAnalyzer<'a> {
                 self.current_line = location.line;
                 self.current_column = location.column;
 
+                // `let x = x + 1;` is only valid when it shadows an existing `x`
+                if self.lookup_variable(name).is_none()
+                    && let Some(use_location) = find_identifier(value, name)
+                {
+                    return Err(format!(
+                        "{}:{}:{}: Error: cannot use '{}' in its own initializer
+Note: '{}' is not defined until this 'let' completes",
+                        self.filename, use_location.line, use_location.column, name, name
+                    ));
+                }
+
                 self.visit(value)?;
 
                 if let AstNode::Identifier { name: var_name, .. } = value.as_ref() {
@@ -86,14 +215,38 @@ impl<'a> SemanticAnalyzer<'a> {
                     self.consume_variable(var_name)?;
                 }
 
-                let var_type = type_annotation.clone().unwrap_or_else(|| {
+                if let Some(annotation) = type_annotation {
+                    self.check_strict_value(name, annotation, value)?;
+                }
+                let mut var_type = type_annotation.clone().unwrap_or_else(|| {
                     self.infer_type(value)
                 });
+                // Arrays never change length, so `let e: [int] = [];` stays empty;
+                // keeping the size lets `min`/`max` reject it
+                if matches!(value.as_ref(), AstNode::ArrayLit(elements) if elements.is_empty())
+                    && let Some(element) = var_type.strip_prefix('[').and_then(|t| t.strip_suffix(']'))
+                    && !element.contains(';')
+                {
+                    var_type = format!("[{}; 0]", element);
+                }
 
+                self.warn_same_scope_shadow(name, location);
                 self.declare_variable(name, *mutable, var_type, location.line, location.column);
                 Ok(())
             }
 
+            AstNode::LetPattern { pattern, value, location } => {
+                self.visit(value)?;
+                self.current_line = location.line;
+                self.current_column = location.column;
+                let binding_type = self.check_enum_pattern(pattern, value)?;
+                self.check_irrefutable(pattern)?;
+                if let Pattern::EnumPattern { binding: Some(binding), .. } = pattern {
+                    self.declare_variable(binding, false, binding_type, location.line, location.column);
+                }
+                Ok(())
+            }
+
             AstNode::Assignment { name, value, location } => {
                 self.current_line = location.line;
                 self.current_column = location.column;
@@ -103,6 +256,9 @@ impl<'a> SemanticAnalyzer<'a> {
                 self.check_is_mutable(name)?;
                 self.check_not_borrowed(name)?;
                 self.visit(value)?;
+                if let Some(var_type) = self.get_type(name).map(str::to_string) {
+                    self.check_strict_value(name, &var_type, value)?;
+                }
 
                 if let AstNode::Identifier { name: var_name, .. } = value.as_ref() {
                     self.check_not_consumed(var_name)?;
@@ -119,6 +275,7 @@ impl<'a> SemanticAnalyzer<'a> {
                 self.check_variable_exists(array)?;
                 self.check_not_consumed(array)?;
                 self.check_is_mutable(array)?;
+                self.mark_used(array);
                 self.visit(index)?;
                 self.visit(value)?;
                 Ok(())
@@ -127,7 +284,21 @@ impl<'a> SemanticAnalyzer<'a> {
             AstNode::Block(statements) => {
                 self.push_scope();
                 for stmt in statements {
+                    if let AstNode::FunctionDef { name, .. } = stmt {
+                        self.local_functions.last_mut().unwrap().insert(name.clone());
+                    }
+                }
+                let mut diverged = false;
+                for stmt in statements {
+                    let unreachable = diverged && !matches!(stmt, AstNode::FunctionDef { .. });
                     self.visit(stmt)?;
+                    if unreachable {
+                        self.warn_unreachable(stmt);
+                        diverged = false;
+                    }
+                    if matches!(stmt, AstNode::Return(_) | AstNode::Break { .. } | AstNode::Continue { .. }) {
+                        diverged = true;
+                    }
                 }
                 self.pop_scope();
                 Ok(())
@@ -135,6 +306,7 @@ impl<'a> SemanticAnalyzer<'a> {
 
             AstNode::If { condition, then_block, else_block } => {
                 self.visit(condition)?;
+                self.check_strict_condition(condition)?;
                 self.visit(then_block)?;
                 if let Some(else_block) = else_block {
                     self.visit(else_block)?;
@@ -142,20 +314,65 @@ impl<'a> SemanticAnalyzer<'a> {
                 Ok(())
             }
 
-            AstNode::While { condition, body } => {
+            AstNode::IfExpr { condition, then_value, else_value } => {
                 self.visit(condition)?;
+                self.check_strict_condition(condition)?;
+                self.visit(then_value)?;
+                self.visit(else_value)?;
+
+                let then_type = self.infer_type(then_value);
+                let else_type = self.infer_type(else_value);
+                let compatible = then_type == else_type || (is_numeric(&then_type) && is_numeric(&else_type));
+                if then_type != "unknown" && else_type != "unknown" && !compatible {
+                    return Err(format!(
+                        "{}:{}:{}: Error: mismatched types: 'if' branches have types '{}' and '{}'
+Note: both branches of an 'if' used as a value must have the same type",
+                        self.filename, self.current_line, self.current_column, then_type, else_type
+                    ));
+                }
+                Ok(())
+            }
+
+            AstNode::IfLet { pattern, value, then_block, else_block } => {
+                self.visit(value)?;
+                let binding_type = self.check_enum_pattern(pattern, value)?;
+
+                self.push_scope();
+                if let Pattern::EnumPattern { binding: Some(binding), .. } = pattern {
+                    self.declare_variable(binding, false, binding_type, self.current_line, self.current_column);
+                }
+                self.visit(then_block)?;
+                self.pop_scope();
+
+                if let Some(else_block) = else_block {
+                    self.visit(else_block)?;
+                }
+                Ok(())
+            }
+
+            AstNode::While { condition, body, else_block } => {
+                self.visit(condition)?;
+                self.check_strict_condition(condition)?;
                 let was_in_loop = self.in_loop;
                 self.in_loop = true;
                 self.visit(body)?;
                 self.in_loop = was_in_loop;
+                if let Some(else_block) = else_block {
+                    self.visit(else_block)?;
+                }
                 Ok(())
             }
 
             AstNode::For { variable, iterator, body } => {
                 self.visit(iterator)?;
+                let element_type = element_type(&self.infer_type(iterator)).unwrap_or_else(|| "int".to_string());
                 self.push_scope();
 
-                self.declare_variable(variable, false, "int".to_string(), self.current_line, self.current_column);
+                // The loop owns its variable: reassigning it in the body is rejected
+                self.declare_variable(variable, false, element_type, self.current_line, self.current_column);
+                if let Some(info) = self.symbol_table.last_mut().and_then(|scope| scope.get_mut(variable)) {
+                    info.is_loop_variable = true;
+                }
 
                 let was_in_loop = self.in_loop;
                 self.in_loop = true;
@@ -169,7 +386,29 @@ impl<'a> SemanticAnalyzer<'a> {
             AstNode::Match { value, arms } => {
                 self.visit(value)?;
                 for arm in arms {
+                    self.push_scope();
+                    match &arm.pattern {
+                        Pattern::EnumPattern { binding: Some(binding), .. } => {
+                            let binding_type = self.check_enum_pattern(&arm.pattern, value)?;
+                            self.declare_variable(binding, false, binding_type, self.current_line, self.current_column);
+                        }
+                        Pattern::Identifier(binding) => {
+                            let var_type = self.infer_type(value);
+                            self.declare_variable(binding, false, var_type, self.current_line, self.current_column);
+                        }
+                        Pattern::StringLiteral(literal) => {
+                            let value_type = self.infer_type(value);
+                            if value_type != "unknown" && value_type != "string" {
+                                return Err(format!(
+                                    "{}:{}:{}: Error: mismatched types: expected '{}', found string pattern \"{}\"",
+                                    self.filename, self.current_line, self.current_column, value_type, literal
+                                ));
+                            }
+                        }
+                        _ => {}
+                    }
                     self.visit(&arm.body)?;
+                    self.pop_scope();
                 }
                 Ok(())
             }
@@ -178,24 +417,26 @@ impl<'a> SemanticAnalyzer<'a> {
                 if let Some(value) = value {
                     self.visit(value)?;
                 }
-                Ok(())
+                self.check_return_type(value.as_deref())
             }
 
-            AstNode::Break => {
+            AstNode::Break { location } => {
                 if !self.in_loop {
                     return Err(format!(
-                        "{}:{}:{}: Error: 'break' outside of loop",
-                        self.filename, self.current_line, self.current_column
+                        "{}:{}:{}: Error: 'break' outside of loop
+Note: 'break' can only be used inside a 'while' or 'for' body",
+                        self.filename, location.line, location.column
                     ));
                 }
                 Ok(())
             }
 
-            AstNode::Continue => {
+            AstNode::Continue { location } => {
                 if !self.in_loop {
                     return Err(format!(
-                        "{}:{}:{}: Error: 'continue' outside of loop",
-                        self.filename, self.current_line, self.current_column
+                        "{}:{}:{}: Error: 'continue' outside of loop
+Note: 'continue' can only be used inside a 'while' or 'for' body",
+                        self.filename, location.line, location.column
                     ));
                 }
                 Ok(())
@@ -206,25 +447,40 @@ impl<'a> SemanticAnalyzer<'a> {
             AstNode::BinaryOp { left, right, op } => {
                 self.visit(left)?;
                 self.visit(right)?;
+                self.check_operand_types(op, left, right)?;
 
                 if matches!(op, BinOp::Add) {
-                    if let AstNode::Identifier { name: var, .. } = left.as_ref() {
-                        if self.get_type(var) == Some("string") {
-                            self.consume_variable(var)?;
-                        }
+                    if let AstNode::Identifier { name: var, .. } = left.as_ref()
+                        && self.get_type(var) == Some("string")
+                    {
+                        self.consume_variable(var)?;
                     }
-                    if let AstNode::Identifier { name: var, .. } = right.as_ref() {
-                        if self.get_type(var) == Some("string") {
-                            self.consume_variable(var)?;
-                        }
+                    if let AstNode::Identifier { name: var, .. } = right.as_ref()
+                        && self.get_type(var) == Some("string")
+                    {
+                        self.consume_variable(var)?;
                     }
                 }
 
                 Ok(())
             }
 
-            AstNode::UnaryOp { operand, .. } => {
+            AstNode::UnaryOp { op, operand } => {
                 self.visit(operand)?;
+
+                let (symbol, expected) = match op {
+                    UnOp::Not => ("!", "bool"),
+                    UnOp::Negate => ("-", "int"),
+                };
+                let found = self.infer_type(operand);
+                let negates_float = matches!(op, UnOp::Negate) && found == "float";
+                if found != "unknown" && found != expected && !negates_float {
+                    return Err(format!(
+                        "{}:{}:{}: Error: cannot apply unary '{}' to type '{}'
+Note: '{}' expects an operand of type '{}'",
+                        self.filename, self.current_line, self.current_column, symbol, found, symbol, expected
+                    ));
+                }
                 Ok(())
             }
 
@@ -234,43 +490,146 @@ impl<'a> SemanticAnalyzer<'a> {
 
                 self.check_variable_exists(name)?;
                 self.check_not_consumed(name)?;
+                self.mark_used(name);
                 Ok(())
             }
 
-            AstNode::Reference(expr) => {
+            AstNode::Reference { value: expr, mutable } => {
                 if let AstNode::Identifier { name: var_name, .. } = expr.as_ref() {
                     self.check_not_consumed(var_name)?;
+                    if *mutable {
+                        self.check_mutable_borrow(var_name)?;
+                    }
                     self.borrow_variable(var_name)?;
                 }
                 self.visit(expr)?;
                 Ok(())
             }
 
-            AstNode::Call { name: _, args } => {
+            AstNode::Call { name, args, location } => {
+                self.current_line = location.line;
+                self.current_column = location.column;
+
+                if name == "format" {
+                    self.check_format_call(args)?;
+                }
+
+                let builtin_arity = match name.as_str() {
+                    "print" | "println" | "len" | "to_chars" | "from_chars" | "min" | "max" => Some(1),
+                    "assert_eq" | "mod" | "bench" => Some(2),
+                    "clamp" | "lerp" | "between" => Some(3),
+                    _ => None,
+                };
+                if let Some(arity) = builtin_arity
+                    && args.len() != arity
+                {
+                    return Err(format!(
+                        "{}:{}:{}: Error: '{}' expects {} arguments but {} were supplied",
+                        self.filename, self.current_line, self.current_column, name, arity, args.len()
+                    ));
+                }
+
+                let is_builtin = matches!(
+                    name.as_str(),
+                    "puts" | "print_int" | "print" | "println" | "read_file" | "write_file" | "format"
+                        | "assert_eq" | "len" | "to_chars" | "from_chars" | "clamp" | "lerp" | "mod" | "min"
+                        | "max" | "bench" | "between" | "cfg"
+                );
+                if !is_builtin
+                    && !self.functions.contains_key(name)
+                    && !self.local_functions.iter().any(|scope| scope.contains(name))
+                {
+                    return Err(format!(
+                        "{}:{}:{}: Error: cannot find function '{}' in this scope",
+                        self.filename, self.current_line, self.current_column, name
+                    ));
+                }
+
+                if !self.local_functions.iter().any(|scope| scope.contains(name))
+                    && let Some(params) = self.functions.get(name)
+                {
+                    self.check_call_arity(name, params, args.len())?;
+                }
+
+                // A nested function of the same name shadows the deprecated top-level one
+                if let Some(note) = self.deprecated.get(name)
+                    && !self.local_functions.iter().any(|scope| scope.contains(name))
+                {
+                    let note = if note.is_empty() { String::new() } else { format!(": {}", note) };
+                    self.warnings.push(format!(
+                        "{}:{}:{}: Warning: call to deprecated function '{}'{}",
+                        self.filename, self.current_line, self.current_column, name, note
+                    ));
+                }
+
+                if name == "cfg" {
+                    return Err(format!(
+                        "{}:{}:{}: Error: 'cfg(\"...\")' can only be used as the condition of an 'if'",
+                        self.filename, self.current_line, self.current_column
+                    ));
+                }
+
+                if name == "min" || name == "max" {
+                    self.check_array_reduction(name, &args[0])?;
+                }
+
+                if name == "bench" {
+                    self.check_bench_target(&args[1])?;
+                }
+
+                if name == "between" {
+                    self.check_between_args(args)?;
+                }
+
+                if name == "clamp" || name == "lerp" {
+                    self.check_numeric_args(name, args)?;
+                }
+
+                // Borrows passed as arguments only last for the duration of the call
+                let mut call_borrows = Vec::new();
                 for arg in args.iter() {
-                    if let AstNode::Reference(ref_expr) = arg {
-                        if let AstNode::Identifier { name: var_name, .. } = ref_expr.as_ref() {
+                    if let AstNode::Reference { value: ref_expr, mutable } = arg {
+                        if let AstNode::Identifier { name: var_name, location } = ref_expr.as_ref() {
+                            self.current_line = location.line;
+                            self.current_column = location.column;
+                            self.check_variable_exists(var_name)?;
                             self.check_not_consumed(var_name)?;
+                            if *mutable {
+                                self.check_mutable_borrow(var_name)?;
+                            }
                             self.borrow_variable(var_name)?;
+                            call_borrows.push(var_name.clone());
                         }
                     } else {
                         self.visit(arg)?;
-                        if let AstNode::Identifier { name: var_name, .. } = arg {
-                            if !self.is_copy_type(var_name) {
-                                self.check_not_consumed(var_name)?;
-                                self.consume_variable(var_name)?;
-                            }
+                        // These builtins only read their argument, so it stays usable afterwards
+                        let reads_only = matches!(
+                            name.as_str(),
+                            "print" | "println" | "len" | "to_chars" | "from_chars" | "min" | "max"
+                        );
+                        if let AstNode::Identifier { name: var_name, .. } = arg
+                            && !reads_only
+                            && !self.is_copy_type(var_name)
+                        {
+                            self.check_not_consumed(var_name)?;
+                            self.consume_variable(var_name)?;
                         }
                     }
                 }
+                for var_name in call_borrows {
+                    self.release_borrow(&var_name);
+                }
                 Ok(())
             }
 
-            AstNode::MethodCall { object, args, .. } => {
+            AstNode::MethodCall { object, method, args } => {
                 self.visit(object)?;
                 for arg in args {
                     self.visit(arg)?;
                 }
+                if method == "contains" || method == "index_of" {
+                    self.check_array_search(method, object, args)?;
+                }
                 Ok(())
             }
 
@@ -285,6 +644,27 @@ impl<'a> SemanticAnalyzer<'a> {
             AstNode::ArrayLit(elements) => {
                 for elem in elements {
                     self.visit(elem)?;
+                    if self.infer_type(elem) == "float" {
+                        return Err(format!(
+                            "{}:{}:{}: Error: arrays of 'float' are not supported
+Note: array elements are stored as 64-bit integers",
+                            self.filename, self.current_line, self.current_column
+                        ));
+                    }
+                }
+                Ok(())
+            }
+
+            AstNode::Range { start, end } => {
+                for bound in [start, end] {
+                    self.visit(bound)?;
+                    let bound_type = self.infer_type(bound);
+                    if bound_type != "int" && bound_type != "unknown" {
+                        return Err(format!(
+                            "{}:{}:{}: Error: mismatched types: range bounds must be 'int', found '{}'",
+                            self.filename, self.current_line, self.current_column, bound_type
+                        ));
+                    }
                 }
                 Ok(())
             }
@@ -303,10 +683,31 @@ impl<'a> SemanticAnalyzer<'a> {
                 Ok(())
             }
 
+            AstNode::SizeOf(type_name) => self.check_type_exists(type_name),
+
+            AstNode::Try(expr) => {
+                self.visit(expr)?;
+                if self.return_type.as_deref() != Some("Result") {
+                    return Err(format!(
+                        "{}:{}:{}: Error: the '?' operator can only be used in a function that returns 'Result'",
+                        self.filename, self.current_line, self.current_column
+                    ));
+                }
+                Ok(())
+            }
+
+            AstNode::Alias { target, location, .. } => {
+                self.current_line = location.line;
+                self.current_column = location.column;
+                self.check_type_exists(target)
+            }
+
+            AstNode::Import { .. } => Ok(()),
+            AstNode::ModuleDoc(_) => Ok(()),
             AstNode::StructDef { .. } => Ok(()),
             AstNode::EnumDef { .. } => Ok(()),
             AstNode::ArrayType { .. } => Ok(()),
-            AstNode::Number(_) => Ok(()),
+            AstNode::Number(_) | AstNode::Float(_) => Ok(()),
             AstNode::Boolean(_) => Ok(()),
             AstNode::Character(_) => Ok(()),
             AstNode::StringLit(_) => Ok(()),
@@ -321,9 +722,11 @@ impl<'a> SemanticAnalyzer<'a> {
                 is_consumed: false,
                 borrow_count: 0,
                 is_mutable: mutable,
+                is_loop_variable: false,
                 declared_line: line,
                 declared_column: column,
                 var_type,
+                was_used: false,
             },
         );
     }
@@ -338,50 +741,488 @@ impl<'a> SemanticAnalyzer<'a> {
         Ok(())
     }
 
+    fn check_type_exists(&self, type_name: &str) -> Result<(), String> {
+        let base = match type_name.strip_prefix('[') {
+            Some(inner) => inner.trim_end_matches(']').split(';').next().unwrap_or("").trim(),
+            None => type_name,
+        };
+
+        if matches!(base, "int" | "float" | "bool" | "char" | "string") || self.user_types.contains(base) {
+            return Ok(());
+        }
+
+        if type_name.starts_with('[') {
+            return self.check_type_exists(base);
+        }
+
+        Err(format!(
+            "{}:{}:{}: Error: cannot find type '{}' in this scope",
+            self.filename, self.current_line, self.current_column, type_name
+        ))
+    }
+
     fn check_not_consumed(&self, name: &str) -> Result<(), String> {
         if self.is_copy_type(name) {
             return Ok(());
         }
 
-        if let Some(info) = self.lookup_variable(name) {
-            if info.is_consumed {
-                return Err(format!(
-                    "{}:{}:{}: Error: use of moved value '{}'
+        if let Some(info) = self.lookup_variable(name)
+            && info.is_consumed
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: use of moved value '{}'
     Note: value moved at line {}, cannot be used again
     Help: Consider borrowing '&{}' to keep ownership in the current scope",
-                    self.filename, self.current_line, self.current_column, name, info.declared_line, name
+                self.filename, self.current_line, self.current_column, name, info.declared_line, name
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_is_mutable(&self, name: &str) -> Result<(), String> {
+        if let Some(info) = self.lookup_variable(name)
+            && info.is_loop_variable
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot assign to loop variable '{}'
+Note: the value of a 'for' loop variable is controlled by the loop",
+                self.filename, self.current_line, self.current_column, name
+            ));
+        }
+
+        if let Some(info) = self.lookup_variable(name)
+            && !info.is_mutable
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot assign to immutable variable '{}'
+Help: Consider declaring with 'let mut {}'",
+                self.filename, self.current_line, self.current_column, name, name
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_mutable_borrow(&self, name: &str) -> Result<(), String> {
+        if let Some(info) = self.lookup_variable(name)
+            && (!info.is_mutable || info.is_loop_variable)
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot borrow '{}' as mutable, as it is not declared as mutable
+Help: Consider declaring with 'let mut {}'",
+                self.filename, self.current_line, self.current_column, name, name
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_not_borrowed(&self, name: &str) -> Result<(), String> {
+        if let Some(info) = self.lookup_variable(name)
+            && info.borrow_count > 0
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot move '{}' while borrowed
+Note: {} active borrow(s) exist",
+                self.filename, self.current_line, self.current_column, name, info.borrow_count
+            ));
+        }
+        Ok(())
+    }
+
+    /// `--strict`: an `if`/`while` condition must be a bool rather than an int tested
+    /// against zero.
+    fn check_strict_condition(&self, condition: &AstNode) -> Result<(), String> {
+        let condition_type = self.infer_type(condition);
+        if self.strict && condition_type != "bool" && condition_type != "unknown" {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: expected a 'bool' condition, found '{}'
+Note: --strict does not treat non-zero values as true; compare explicitly, e.g. 'x != 0'",
+                self.filename, self.current_line, self.current_column, condition_type
+            ));
+        }
+        Ok(())
+    }
+
+    /// `--strict`: a value stored in `name` must have the variable's declared type.
+    fn check_strict_value(&self, name: &str, expected: &str, value: &AstNode) -> Result<(), String> {
+        let found = self.infer_type(value);
+        if self.strict && found != expected && found != "unknown" {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: '{}' is '{}' but the value is '{}'
+Note: --strict does not convert between types implicitly",
+                self.filename, self.current_line, self.current_column, name, expected, found
+            ));
+        }
+        Ok(())
+    }
+
+    /// Arithmetic needs ints (or two strings for `+`), comparisons need operands of
+    /// the same type and `&&`/`||` need bools. Operands of unknown type are not checked.
+    fn check_operand_types(&self, op: &BinOp, left: &AstNode, right: &AstNode) -> Result<(), String> {
+        let left_type = self.infer_type(left);
+        let right_type = self.infer_type(right);
+        if left_type == "unknown" || right_type == "unknown" {
+            return Ok(());
+        }
+
+        // Concatenation copies both operands with strcpy/strcat, so anything
+        // that is not a string must be rejected before it reaches codegen
+        if matches!(op, BinOp::Add) && (left_type == "string") != (right_type == "string") {
+            let other = if left_type == "string" { &right_type } else { &left_type };
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: cannot concatenate 'string' and '{}'
+Note: '+' on strings only joins two 'string' operands",
+                self.filename, self.current_line, self.current_column, other
+            ));
+        }
+
+        // An 'int' operand next to a 'float' is promoted to 'float', except under --strict
+        let numeric = is_numeric(&left_type) && is_numeric(&right_type);
+        let comparable = left_type == right_type || numeric;
+        let (symbol, valid, expectation) = match op {
+            BinOp::Add => (
+                "+",
+                numeric || (left_type == "string" && right_type == "string"),
+                "two numeric or two 'string' operands",
+            ),
+            BinOp::Sub => ("-", numeric, "'int' or 'float' operands"),
+            BinOp::Mul => ("*", numeric, "'int' or 'float' operands"),
+            BinOp::Div => ("/", numeric, "'int' or 'float' operands"),
+            BinOp::Mod => ("%", numeric, "'int' or 'float' operands"),
+            BinOp::Pow => ("**", numeric, "'int' or 'float' operands"),
+            BinOp::Equal => ("==", comparable, "operands of the same type"),
+            BinOp::NotEqual => ("!=", comparable, "operands of the same type"),
+            BinOp::LessThan => ("<", comparable, "operands of the same type"),
+            BinOp::LessEqual => ("<=", comparable, "operands of the same type"),
+            BinOp::GreaterThan => (">", comparable, "operands of the same type"),
+            BinOp::GreaterEqual => (">=", comparable, "operands of the same type"),
+            BinOp::And => ("&&", left_type == "bool" && right_type == "bool", "'bool' operands"),
+            BinOp::Or => ("||", left_type == "bool" && right_type == "bool", "'bool' operands"),
+        };
+
+        if self.strict && numeric && left_type != right_type {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: cannot apply '{}' to '{}' and '{}'
+Note: --strict does not promote 'int' to 'float'; write the int as a float literal, e.g. '2.0'",
+                self.filename, self.current_line, self.current_column, symbol, left_type, right_type
+            ));
+        }
+
+        let is_ordering = matches!(op, BinOp::LessThan | BinOp::LessEqual | BinOp::GreaterThan | BinOp::GreaterEqual);
+        if valid && is_ordering && self.user_types.contains(&left_type) {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot compare '{}' values with '{}'
+Note: values of user-defined types only support '==' and '!='",
+                self.filename, self.current_line, self.current_column, left_type, symbol
+            ));
+        }
+
+        if !valid {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: cannot apply '{}' to '{}' and '{}'
+Note: '{}' expects {}",
+                self.filename, self.current_line, self.current_column, symbol, left_type, right_type, symbol, expectation
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_return_type(&self, value: Option<&AstNode>) -> Result<(), String> {
+        match (&self.return_type, value) {
+            (None, Some(_)) => Err(format!(
+                "{}:{}:{}: Error: mismatched types: function has no return type but returns a value
+Help: Consider adding a return type, e.g. '-> int'",
+                self.filename, self.current_line, self.current_column
+            )),
+            (Some(expected), None) => Err(format!(
+                "{}:{}:{}: Error: mismatched types: expected '{}', found no return value",
+                self.filename, self.current_line, self.current_column, expected
+            )),
+            (Some(expected), Some(value)) => {
+                let found = self.infer_type(value);
+                if found != "unknown" && found != *expected {
+                    return Err(format!(
+                        "{}:{}:{}: Error: mismatched types: expected '{}', found '{}'",
+                        self.filename, self.current_line, self.current_column, expected, found
+                    ));
+                }
+                Ok(())
+            }
+            (None, None) => Ok(()),
+        }
+    }
+
+    /// A call may leave out trailing parameters that have default values, but no others.
+    fn check_call_arity(&self, name: &str, params: &[Parameter], supplied: usize) -> Result<(), String> {
+        let required = params.iter().filter(|p| p.default_value.is_none()).count();
+        if (required..=params.len()).contains(&supplied) {
+            return Ok(());
+        }
+        let expected = if required == params.len() {
+            required.to_string()
+        } else {
+            format!("{} to {}", required, params.len())
+        };
+        Err(format!(
+            "{}:{}:{}: Error: '{}' expects {} arguments but {} were supplied",
+            self.filename, self.current_line, self.current_column, name, expected, supplied
+        ))
+    }
+
+    /// Defaults are evaluated at each call site, so they cannot refer to variables,
+    /// and must have the parameter's type.
+    fn check_default_values(&self, params: &[Parameter]) -> Result<(), String> {
+        for param in params {
+            let Some(value) = &param.default_value else {
+                continue;
+            };
+            if let Some(location) = find_any_identifier(value) {
+                return Err(format!(
+                    "{}:{}:{}: Error: default value for '{}' cannot refer to variables",
+                    self.filename, location.line, location.column, param.name
+                ));
+            }
+            let value_type = self.infer_type(value);
+            if value_type != "unknown" && value_type != param.param_type {
+                return Err(format!(
+                    "{}:{}:{}: Error: mismatched types: default value for '{}' is '{}' but the parameter is '{}'",
+                    self.filename, self.current_line, self.current_column, param.name, value_type, param.param_type
                 ));
             }
         }
         Ok(())
     }
 
-    fn check_is_mutable(&self, name: &str) -> Result<(), String> {
-        if let Some(info) = self.lookup_variable(name) {
-            if !info.is_mutable {
+    /// `bench(n, "name")` calls `name` with no arguments, so it must be a
+    /// top-level function without parameters.
+    fn check_bench_target(&self, target: &AstNode) -> Result<(), String> {
+        let AstNode::StringLit(function) = target else {
+            return Err(format!(
+                "{}:{}:{}: Error: 'bench' expects the function name as a string literal",
+                self.filename, self.current_line, self.current_column
+            ));
+        };
+        match self.functions.get(function) {
+            None => Err(format!(
+                "{}:{}:{}: Error: cannot bench '{}': no function with that name",
+                self.filename, self.current_line, self.current_column, function
+            )),
+            Some(params) if !params.is_empty() => Err(format!(
+                "{}:{}:{}: Error: cannot bench '{}': it takes {} parameter{}
+Note: 'bench' calls its function with no arguments",
+                self.filename, self.current_line, self.current_column, function, params.len(),
+                if params.len() == 1 { "" } else { "s" }
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// `arr.contains(x)` / `arr.index_of(x)` search for one value of the element type.
+    fn check_array_search(&self, method: &str, array: &AstNode, args: &[AstNode]) -> Result<(), String> {
+        if args.len() != 1 {
+            return Err(format!(
+                "{}:{}:{}: Error: '{}' expects 1 argument but {} were supplied",
+                self.filename, self.current_line, self.current_column, method, args.len()
+            ));
+        }
+
+        let array_type = self.infer_type(array);
+        let value_type = self.infer_type(&args[0]);
+        if array_type == "unknown" {
+            return Ok(());
+        }
+        let Some(inner) = array_type.strip_prefix('[').and_then(|t| t.strip_suffix(']')) else {
+            return Err(format!(
+                "{}:{}:{}: Error: no method '{}' on type '{}'",
+                self.filename, self.current_line, self.current_column, method, array_type
+            ));
+        };
+
+        let element_type = inner.split(';').next().unwrap_or("").trim();
+        if value_type != "unknown" && value_type != element_type {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: cannot search '{}' for a '{}'",
+                self.filename, self.current_line, self.current_column, array_type, value_type
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks the pattern of a destructuring `let` or `if let`: the variant must exist
+    /// and `value` must be of that enum. Returns the type of the bound payload.
+    fn check_enum_pattern(&self, pattern: &Pattern, value: &AstNode) -> Result<String, String> {
+        let Pattern::EnumPattern { enum_name, variant, binding } = pattern else {
+            return Err(format!(
+                "{}:{}:{}: Error: expected an enum pattern such as 'Option::Some(x)'",
+                self.filename, self.current_line, self.current_column
+            ));
+        };
+        let Some(variants) = self.enum_variants.get(enum_name) else {
+            return Err(format!(
+                "{}:{}:{}: Error: unknown enum '{}' in pattern",
+                self.filename, self.current_line, self.current_column, enum_name
+            ));
+        };
+        let Some(matched) = variants.iter().find(|v| &v.name == variant) else {
+            return Err(format!(
+                "{}:{}:{}: Error: enum '{}' has no variant '{}'",
+                self.filename, self.current_line, self.current_column, enum_name, variant
+            ));
+        };
+
+        let value_type = self.infer_type(value);
+        if value_type != "unknown" && &value_type != enum_name {
+            return Err(format!(
+                "{}:{}:{}: Error: mismatched types: expected '{}', found '{}'",
+                self.filename, self.current_line, self.current_column, enum_name, value_type
+            ));
+        }
+
+        if binding.is_some() && matched.value_type.is_none() {
+            return Err(format!(
+                "{}:{}:{}: Error: variant '{}::{}' has no value to bind",
+                self.filename, self.current_line, self.current_column, enum_name, variant
+            ));
+        }
+        Ok(matched.value_type.clone().unwrap_or_else(|| "unknown".to_string()))
+    }
+
+    /// A plain `let` cannot fall through, so its pattern must name the enum's only variant.
+    fn check_irrefutable(&self, pattern: &Pattern) -> Result<(), String> {
+        if let Pattern::EnumPattern { enum_name, variant, .. } = pattern
+            && let Some(variants) = self.enum_variants.get(enum_name)
+            && let Some(other) = variants.iter().find(|v| &v.name != variant)
+        {
+            return Err(format!(
+                "{}:{}:{}: Error: refutable pattern in 'let': '{}::{}' is not covered
+Help: use 'if let' or 'match' to handle the other variants",
+                self.filename, self.current_line, self.current_column, enum_name, other.name
+            ));
+        }
+        Ok(())
+    }
+
+    /// `between(x, lo, hi)` compares all three values, so they must share one ordered type.
+    fn check_between_args(&self, args: &[AstNode]) -> Result<(), String> {
+        let types: Vec<String> = args.iter().map(|arg| self.infer_type(arg)).collect();
+        let known: Vec<&String> = types.iter().filter(|t| *t != "unknown").collect();
+
+        if let Some(first) = known.first() {
+            if !matches!(first.as_str(), "int" | "char") {
                 return Err(format!(
-                    "{}:{}:{}: Error: cannot assign to immutable variable '{}'
-Help: Consider declaring with 'let mut {}'",
-                    self.filename, self.current_line, self.current_column, name, name
+                    "{}:{}:{}: Error: 'between' expects 'int' or 'char' values, found '{}'",
+                    self.filename, self.current_line, self.current_column, first
+                ));
+            }
+            if let Some(other) = known.iter().find(|t| *t != first) {
+                return Err(format!(
+                    "{}:{}:{}: Error: mismatched types: 'between' arguments must all have the same type, found '{}' and '{}'",
+                    self.filename, self.current_line, self.current_column, first, other
                 ));
             }
         }
         Ok(())
     }
 
-    fn check_not_borrowed(&self, name: &str) -> Result<(), String> {
-        if let Some(info) = self.lookup_variable(name) {
-            if info.borrow_count > 0 {
+    /// `clamp` and `lerp` take 'int' or 'float' arguments; a mix is promoted to 'float'.
+    fn check_numeric_args(&self, name: &str, args: &[AstNode]) -> Result<(), String> {
+        for arg in args {
+            let arg_type = self.infer_type(arg);
+            if arg_type != "unknown" && !is_numeric(&arg_type) {
                 return Err(format!(
-                    "{}:{}:{}: Error: cannot move '{}' while borrowed
-Note: {} active borrow(s) exist",
-                    self.filename, self.current_line, self.current_column, name, info.borrow_count
+                    "{}:{}:{}: Error: '{}' expects 'int' or 'float' arguments, found '{}'",
+                    self.filename, self.current_line, self.current_column, name, arg_type
                 ));
             }
         }
         Ok(())
     }
 
+    /// `min`/`max` reduce a non-empty array of ints to one element.
+    fn check_array_reduction(&self, name: &str, array: &AstNode) -> Result<(), String> {
+        if let AstNode::ArrayLit(elements) = array
+            && let Some(first) = elements.first()
+        {
+            let first_type = self.infer_type(first);
+            for element in &elements[1..] {
+                let element_type = self.infer_type(element);
+                if first_type != "unknown" && element_type != "unknown" && element_type != first_type {
+                    return Err(format!(
+                        "{}:{}:{}: Error: mismatched types: array elements must all be '{}', found '{}'",
+                        self.filename, self.current_line, self.current_column, first_type, element_type
+                    ));
+                }
+            }
+        }
+
+        let array_type = self.infer_type(array);
+        if array_type == "unknown" {
+            return Ok(());
+        }
+        let Some(inner) = array_type.strip_prefix('[').and_then(|t| t.strip_suffix(']')) else {
+            return Err(format!(
+                "{}:{}:{}: Error: '{}' expects an array, found '{}'",
+                self.filename, self.current_line, self.current_column, name, array_type
+            ));
+        };
+
+        let mut parts = inner.split(';');
+        let element_type = parts.next().unwrap_or("").trim();
+        if element_type != "int" {
+            return Err(format!(
+                "{}:{}:{}: Error: '{}' expects an array of 'int', found '{}'
+Note: only 'int' elements can be ordered",
+                self.filename, self.current_line, self.current_column, name, array_type
+            ));
+        }
+        if parts.next().map(str::trim) == Some("0") {
+            return Err(format!(
+                "{}:{}:{}: Error: cannot take the {} of an empty array",
+                self.filename, self.current_line, self.current_column, name
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_format_call(&self, args: &[AstNode]) -> Result<(), String> {
+        let Some(AstNode::StringLit(fmt)) = args.first() else {
+            return Err(format!(
+                "{}:{}:{}: Error: 'format' expects a string literal as its first argument",
+                self.filename, self.current_line, self.current_column
+            ));
+        };
+
+        let pieces = parse_format_string(fmt).map_err(|e| {
+            format!("{}:{}:{}: Error: {}", self.filename, self.current_line, self.current_column, e)
+        })?;
+
+        let expected = placeholder_count(&pieces);
+        if expected != args.len() - 1 {
+            return Err(format!(
+                "{}:{}:{}: Error: format string has {} placeholder(s) but {} argument(s) were supplied",
+                self.filename, self.current_line, self.current_column, expected, args.len() - 1
+            ));
+        }
+
+        // A precision rounds floats and truncates text; whole numbers have nothing to cut
+        let specs = pieces.iter().filter_map(|piece| match piece {
+            FormatPiece::Placeholder(spec) => Some(spec),
+            FormatPiece::Literal(_) => None,
+        });
+        for (spec, arg) in specs.zip(&args[1..]) {
+            let arg_type = self.infer_type(arg);
+            if let Some(precision) = spec.precision
+                && matches!(arg_type.as_str(), "int" | "char")
+            {
+                return Err(format!(
+                    "{}:{}:{}: Error: invalid format spec: precision '.{}' cannot be applied to '{}'\nNote: a precision only applies to 'float', 'string' and 'bool' arguments",
+                    self.filename, self.current_line, self.current_column, precision, arg_type
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
     fn consume_variable(&mut self, name: &str) -> Result<(), String> {
         if self.is_copy_type(name) {
             return Ok(());
@@ -406,12 +1247,22 @@ Note: {} active borrow(s) exist",
         for scope in self.symbol_table.iter_mut().rev() {
             if let Some(info) = scope.get_mut(name) {
                 info.borrow_count += 1;
+                info.was_used = true;
                 return Ok(());
             }
         }
         Ok(())
     }
 
+    fn release_borrow(&mut self, name: &str) {
+        for scope in self.symbol_table.iter_mut().rev() {
+            if let Some(info) = scope.get_mut(name) {
+                info.borrow_count = info.borrow_count.saturating_sub(1);
+                return;
+            }
+        }
+    }
+
     fn lookup_variable(&self, name: &str) -> Option<&VarInfo> {
         for scope in self.symbol_table.iter().rev() {
             if let Some(info) = scope.get(name) {
@@ -428,13 +1279,62 @@ Note: {} active borrow(s) exist",
     fn infer_type(&self, expr: &AstNode) -> String {
         match expr {
             AstNode::Number(_) => "int".to_string(),
+            AstNode::Float(_) => "float".to_string(),
             AstNode::Boolean(_) => "bool".to_string(),
             AstNode::Character(_) => "char".to_string(),
             AstNode::StringLit(_) => "string".to_string(),
             AstNode::Identifier { name, .. } => {
                 self.get_type(name).unwrap_or("unknown").to_string()
             }
-            AstNode::BinaryOp { left, .. } => self.infer_type(left),
+            AstNode::Call { name, .. } if name == "format" || name == "from_chars" => "string".to_string(),
+            AstNode::Call { name, .. } if name == "to_chars" => "[char]".to_string(),
+            AstNode::Call { name, .. } if name == "between" => "bool".to_string(),
+            AstNode::Call { name, .. } if name == "lerp" => "float".to_string(),
+            AstNode::Call { name, args, .. } if name == "clamp" => {
+                if args.iter().any(|arg| self.infer_type(arg) == "float") {
+                    "float".to_string()
+                } else {
+                    "int".to_string()
+                }
+            }
+            AstNode::SizeOf(_) => "int".to_string(),
+            AstNode::Try(_) => "int".to_string(),
+            AstNode::MethodCall { method, .. } if method == "contains" => "bool".to_string(),
+            AstNode::MethodCall { method, .. } if method == "index_of" || method == "len" => "int".to_string(),
+            AstNode::EnumValue { enum_name, .. } => enum_name.clone(),
+            AstNode::Range { .. } => "[int]".to_string(),
+            AstNode::StructInit { name, .. } => name.clone(),
+            AstNode::Call { name, .. } if matches!(name.as_str(), "len" | "min" | "max" | "bench") => "int".to_string(),
+            AstNode::Index { array, .. } => {
+                element_type(&self.infer_type(array)).unwrap_or_else(|| "unknown".to_string())
+            }
+            AstNode::BinaryOp {
+                op: BinOp::Equal | BinOp::NotEqual | BinOp::LessThan | BinOp::LessEqual
+                    | BinOp::GreaterThan | BinOp::GreaterEqual | BinOp::And | BinOp::Or,
+                ..
+            } => "bool".to_string(),
+            // Arithmetic mixing 'int' and 'float' produces a 'float'
+            AstNode::BinaryOp { left, right, .. } => {
+                let left_type = self.infer_type(left);
+                if left_type == "int" && self.infer_type(right) == "float" {
+                    "float".to_string()
+                } else {
+                    left_type
+                }
+            }
+            AstNode::IfExpr { then_value, else_value, .. } => {
+                let then_type = self.infer_type(then_value);
+                if then_type == "int" && self.infer_type(else_value) == "float" {
+                    "float".to_string()
+                } else {
+                    then_type
+                }
+            }
+            AstNode::UnaryOp { op: UnOp::Not, .. } => "bool".to_string(),
+            AstNode::UnaryOp { op: UnOp::Negate, operand } if self.infer_type(operand) == "float" => {
+                "float".to_string()
+            }
+            AstNode::UnaryOp { op: UnOp::Negate, .. } => "int".to_string(),
             AstNode::ArrayLit(elements) => {
                 if elements.is_empty() {
                     "[int; 0]".to_string()
@@ -447,11 +1347,479 @@ Note: {} active borrow(s) exist",
         }
     }
 
+    /// The type of the first `return <value>` in `node`, outside of nested functions.
+    /// The `let`s before it are declared so that a returned variable has a type.
+    fn first_return_type(&mut self, node: &AstNode) -> Option<String> {
+        match node {
+            AstNode::Return(value) => value.as_deref().map(|value| self.infer_type(value)),
+            AstNode::LetBinding { name, value, type_annotation, .. } => {
+                let var_type = type_annotation.clone().unwrap_or_else(|| self.infer_type(value));
+                self.declare_variable(name, false, var_type, 0, 0);
+                None
+            }
+            AstNode::Block(statements) => {
+                self.push_scope();
+                let found = statements.iter().find_map(|statement| self.first_return_type(statement));
+                self.pop_scope();
+                found
+            }
+            AstNode::If { then_block, else_block, .. }
+            | AstNode::IfLet { then_block, else_block, .. }
+            | AstNode::While { body: then_block, else_block, .. } => self
+                .first_return_type(then_block)
+                .or_else(|| else_block.as_deref().and_then(|block| self.first_return_type(block))),
+            AstNode::For { variable, iterator, body } => {
+                let element = element_type(&self.infer_type(iterator)).unwrap_or_else(|| "unknown".to_string());
+                self.push_scope();
+                self.declare_variable(variable, false, element, 0, 0);
+                let found = self.first_return_type(body);
+                self.pop_scope();
+                found
+            }
+            AstNode::Match { arms, .. } => arms.iter().find_map(|arm| self.first_return_type(&arm.body)),
+            _ => None,
+        }
+    }
+
     fn push_scope(&mut self) {
         self.symbol_table.push(HashMap::new());
+        self.local_functions.push(HashSet::new());
     }
 
     fn pop_scope(&mut self) {
-        self.symbol_table.pop();
+        if let Some(scope) = self.symbol_table.pop() {
+            self.warn_unused(scope);
+        }
+        self.local_functions.pop();
+    }
+
+    /// Shadowing in a nested scope is fine, but a second `let` in the same scope
+    /// replaces the first binding and its ownership state along with it.
+    fn warn_same_scope_shadow(&mut self, name: &str, location: &Location) {
+        let Some(previous) = self.symbol_table.last().and_then(|scope| scope.get(name)) else {
+            return;
+        };
+        if previous.is_consumed {
+            return;
+        }
+        let previous_line = previous.declared_line;
+        self.warnings.push(format!(
+            "{}:{}:{}: Warning: '{}' shadows a binding declared on line {} in the same scope\nNote: the binding on line {} can no longer be used after line {}",
+            self.filename, location.line, location.column, name, previous_line, previous_line, location.line
+        ));
+    }
+
+    fn mark_used(&mut self, name: &str) {
+        if let Some(info) = self.symbol_table.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            info.was_used = true;
+        }
+    }
+
+    /// Warns about the variables of a closed scope that were never read, in
+    /// declaration order. Names starting with `_` are deliberately unused.
+    fn warn_unused(&mut self, scope: HashMap<String, VarInfo>) {
+        let mut unused: Vec<(String, VarInfo)> = scope
+            .into_iter()
+            .filter(|(name, info)| !info.was_used && !name.starts_with('_'))
+            .collect();
+        unused.sort_by_key(|(_, info)| (info.declared_line, info.declared_column));
+        for (name, info) in unused {
+            self.warnings.push(format!(
+                "{}:{}:{}: Warning: unused variable '{}'\nHelp: if this is intentional, prefix it with an underscore: '_{}'",
+                self.filename, info.declared_line, info.declared_column, name, name
+            ));
+        }
     }
-}
\ No newline at end of file
+
+    /// Reported once per block, at the first statement after a `return`, `break` or `continue`.
+    /// Called after the statement is visited, so statements without a recorded position fall
+    /// back to the last position seen inside them.
+    fn warn_unreachable(&mut self, stmt: &AstNode) {
+        let stmt = match stmt {
+            AstNode::ExpressionStatement(inner) => inner.as_ref(),
+            other => other,
+        };
+        let location = stmt.location().unwrap_or(Location { line: self.current_line, column: self.current_column });
+        self.warnings.push(format!(
+            "{}:{}:{}: Warning: unreachable statement",
+            self.filename, location.line, location.column
+        ));
+    }
+}
+
+/// The declared return type. An unannotated function that returns a value gets
+/// the type of its first `return <value>`, or `int` when that cannot be inferred.
+/// Codegen declares functions with the same type.
+pub fn effective_return_type(declared: &Option<String>, params: &[Parameter], body: &AstNode) -> Option<String> {
+    if declared.is_some() {
+        return declared.clone();
+    }
+    let mut scratch = SemanticAnalyzer::new("", "", false);
+    for param in params {
+        scratch.declare_variable(&param.name, param.is_mutable, param.param_type.clone(), 0, 0);
+    }
+    scratch
+        .first_return_type(body)
+        .map(|found| if found == "unknown" { "int".to_string() } else { found })
+}
+
+/// Whether every path through `node` ends in a `return`. A `while true` loop
+/// with no `break` of its own never finishes, so it counts as returning.
+fn always_returns(node: &AstNode) -> bool {
+    match node {
+        AstNode::Return(_) => true,
+        AstNode::Block(statements) => statements.iter().any(always_returns),
+        AstNode::If { then_block, else_block: Some(else_block), .. }
+        | AstNode::IfLet { then_block, else_block: Some(else_block), .. } => {
+            always_returns(then_block) && always_returns(else_block)
+        }
+        AstNode::Match { arms, .. } => !arms.is_empty() && arms.iter().all(|arm| always_returns(&arm.body)),
+        AstNode::While { condition, body, .. } => {
+            matches!(condition.as_ref(), AstNode::Boolean(true)) && !breaks_loop(body)
+        }
+        _ => false,
+    }
+}
+
+/// Whether `node` contains a `break` for the loop it is the body of, ignoring
+/// breaks inside nested loops and nested functions.
+fn breaks_loop(node: &AstNode) -> bool {
+    match node {
+        AstNode::Break { .. } => true,
+        AstNode::Block(statements) => statements.iter().any(breaks_loop),
+        AstNode::If { then_block, else_block, .. } | AstNode::IfLet { then_block, else_block, .. } => {
+            breaks_loop(then_block) || else_block.as_deref().is_some_and(breaks_loop)
+        }
+        AstNode::Match { arms, .. } => arms.iter().any(|arm| breaks_loop(&arm.body)),
+        _ => false,
+    }
+}
+
+/// Location of the first use of variable `name` within an expression.
+fn find_identifier<'n>(expr: &'n AstNode, name: &str) -> Option<&'n Location> {
+    find_identifier_matching(expr, &|found| found == name)
+}
+
+/// Location of the first use of any variable within an expression.
+fn find_any_identifier(expr: &AstNode) -> Option<&Location> {
+    find_identifier_matching(expr, &|_| true)
+}
+
+fn find_identifier_matching<'n>(expr: &'n AstNode, matches: &dyn Fn(&str) -> bool) -> Option<&'n Location> {
+    match expr {
+        AstNode::Identifier { name: found, location } if matches(found) => Some(location),
+        AstNode::BinaryOp { left, right, .. } => {
+            find_identifier_matching(left, matches).or_else(|| find_identifier_matching(right, matches))
+        }
+        AstNode::Index { array: inner, index: other } => {
+            find_identifier_matching(inner, matches).or_else(|| find_identifier_matching(other, matches))
+        }
+        AstNode::IfExpr { condition, then_value, else_value } => find_identifier_matching(condition, matches)
+            .or_else(|| find_identifier_matching(then_value, matches))
+            .or_else(|| find_identifier_matching(else_value, matches)),
+        AstNode::UnaryOp { operand: inner, .. }
+        | AstNode::Reference { value: inner, .. }
+        | AstNode::Try(inner)
+        | AstNode::MemberAccess { object: inner, .. }
+        | AstNode::EnumValue { value: Some(inner), .. } => find_identifier_matching(inner, matches),
+        AstNode::MethodCall { object, args, .. } => find_identifier_matching(object, matches)
+            .or_else(|| args.iter().find_map(|arg| find_identifier_matching(arg, matches))),
+        AstNode::Call { args: elements, .. } | AstNode::ArrayLit(elements) => {
+            elements.iter().find_map(|element| find_identifier_matching(element, matches))
+        }
+        AstNode::StructInit { fields, .. } => fields.iter().find_map(|(_, value)| find_identifier_matching(value, matches)),
+        _ => None,
+    }
+}
+
+fn is_numeric(type_name: &str) -> bool {
+    type_name == "int" || type_name == "float"
+}
+
+/// Element type of an array type such as `[int; 3]` or `[string]`.
+fn element_type(array_type: &str) -> Option<String> {
+    let inner = array_type.strip_prefix('[')?;
+    Some(inner.trim_end_matches(']').split(';').next().unwrap_or("").trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> AstNode {
+        let tokens = Lexer::new(source, "test.brn").tokenize().unwrap();
+        Parser::new(tokens, "test.brn").parse().unwrap()
+    }
+
+    /// Analyzes `source` and returns its warnings, panicking if it has errors.
+    fn accepts(source: &str) -> Vec<String> {
+        let mut analyzer = SemanticAnalyzer::new("test.brn", "main", false);
+        if let Err(errors) = analyzer.analyze(&parse(source)) {
+            panic!("unexpected errors: {:#?}", errors);
+        }
+        analyzer.warnings().to_vec()
+    }
+
+    /// Analyzes `source` and returns the first error, panicking if it is accepted.
+    fn rejects(source: &str) -> String {
+        first_error(source, false)
+    }
+
+    /// Like `rejects`, with `--strict` in effect.
+    fn rejects_strict(source: &str) -> String {
+        first_error(source, true)
+    }
+
+    fn first_error(source: &str, strict: bool) -> String {
+        let mut analyzer = SemanticAnalyzer::new("test.brn", "main", strict);
+        analyzer.analyze(&parse(source)).expect_err("expected an error").remove(0)
+    }
+
+    fn main_body(body: &str) -> String {
+        format!("fn main() {{\n{}\n}}\n", body)
+    }
+
+    #[test]
+    fn strict_mode_rejects_implicit_conversions() {
+        let condition = main_body("let n = 1;\nif n {\nprintln(n);\n}");
+        accepts(&condition);
+        assert!(rejects_strict(&condition).contains("mismatched types"));
+        let annotation = main_body("let b: bool = 1;\nprintln(b);");
+        accepts(&annotation);
+        assert!(rejects_strict(&annotation).contains("mismatched types"));
+    }
+
+    #[test]
+    fn mixed_int_float_only_rejected_under_strict() {
+        let source = main_body("let x = 1 + 2.0;\nprintln(x);");
+        accepts(&source);
+        assert!(rejects_strict(&source).contains("cannot apply '+' to 'int' and 'float'"));
+    }
+
+    #[test]
+    fn string_concatenation_needs_two_strings() {
+        accepts(&main_body("let s = \"a\" + \"b\";\nprint(s);"));
+        assert!(rejects(&main_body("let s = \"a\" + [1, 2];\nprint(s);")).contains("cannot concatenate"));
+        let source = main_body("let s = \"n=\" + 5;\nprintln(s);");
+        assert!(rejects(&source).contains("cannot concatenate 'string' and 'int'"));
+        assert!(rejects_strict(&source).contains("cannot concatenate 'string' and 'int'"));
+    }
+
+    #[test]
+    fn float_arrays_rejected() {
+        assert!(rejects(&main_body("let a = [1.0, 2.0];\nprintln(a);")).contains("arrays of 'float' are not supported"));
+    }
+
+    #[test]
+    fn comparison_operand_types() {
+        accepts(&main_body("let b = 3 < 5;\nprint(b);"));
+        accepts(&main_body("let b = \"a\" < \"b\";\nprint(b);"));
+        assert!(rejects(&main_body("let b = \"a\" < 5;\nprint(b);")).contains("cannot apply '<' to 'string' and 'int'"));
+    }
+
+    #[test]
+    fn break_outside_loop() {
+        assert!(rejects(&main_body("break;")).starts_with("test.brn:2:1: Error: 'break' outside of loop"));
+        accepts("enum E { A, B }\nfn main() {\nwhile true {\nlet e = E::A;\nmatch e { E::A => { break; } E::B => {} }\n}\n}\n");
+        let source = "enum E { A, B }\nfn main() {\nlet e = E::A;\nmatch e { E::A => { break; } E::B => {} }\n}\n";
+        assert!(rejects(source).contains("'break' outside of loop"));
+    }
+
+    #[test]
+    fn loop_variable_is_immutable() {
+        assert!(rejects(&main_body("for i in [1, 2, 3] {\ni = 5;\n}")).contains("cannot assign to loop variable 'i'"));
+    }
+
+    #[test]
+    fn max_of_empty_array() {
+        accepts(&main_body("print(max([3, 1, 2]));\nprint(min([3, 1, 2]));"));
+        assert!(rejects(&main_body("print(max([]));")).contains("empty array"));
+        assert!(rejects(&main_body("let empty: [int] = [];\nprintln(max(empty));")).contains("empty array"));
+    }
+
+    #[test]
+    fn builtin_arity_is_checked() {
+        accepts(&main_body("print(clamp(5, 0, 3));"));
+        assert!(rejects(&main_body("print(clamp(5, 0));")).contains("expects 3 arguments"));
+    }
+
+    #[test]
+    fn clamp_and_lerp_argument_types() {
+        accepts(&main_body("println(clamp(5, 0, 3));\nprintln(lerp(0.0, 10.0, 0.5));"));
+        assert!(rejects(&main_body("println(clamp(\"a\", 0, 3));")).contains("found 'string'"));
+        assert!(rejects(&main_body("println(lerp(true, 10.0, 0.5));")).contains("found 'bool'"));
+    }
+
+    #[test]
+    fn between_bounds_share_a_type() {
+        accepts(&main_body("print(between(5, 0, 10));\nprint(between('c', 'a', 'z'));"));
+        assert!(rejects(&main_body("print(between(5, 'a', 10));")).contains("'between' arguments must all have the same type"));
+    }
+
+    #[test]
+    fn self_referential_let() {
+        assert!(rejects(&main_body("let x = x + 1;")).contains("cannot use 'x' in its own initializer"));
+        accepts(&main_body("let x = 1;\nlet x = x + 1;\nprint(x);"));
+    }
+
+    #[test]
+    fn returns_match_the_declared_type() {
+        assert!(rejects("fn f() -> int {\nreturn true;\n}\nfn main() {}\n").contains("mismatched types"));
+        assert!(rejects("fn f() -> int {\nreturn;\n}\nfn main() {}\n").contains("expected 'int', found no return value"));
+    }
+
+    #[test]
+    fn unannotated_function_returning_a_value() {
+        accepts("fn two() {\nreturn 2;\n}\nfn main() {\nprintln(two() + 1);\n}\n");
+        accepts("fn name() {\nreturn \"bob\";\n}\nfn main() {\nlet s: string = name();\nprintln(s);\n}\n");
+        accepts("fn ready(n: int) {\nlet done = n > 2;\nreturn done;\n}\nfn main() {\nif ready(3) {\nprintln(1);\n}\n}\n");
+        let source = "fn name() {\nreturn \"bob\";\n}\nfn f() {\nreturn name();\n}\nfn main() {}\n";
+        assert!(rejects(&format!("{}fn g() {{\nif true {{\nreturn \"a\";\n}}\nreturn 1;\n}}\n", source))
+            .contains("expected 'string', found 'int'"));
+    }
+
+    #[test]
+    fn match_binding_is_scoped_to_its_arm() {
+        let source = "enum Opt { Some(int), None }\n\
+            fn main() {\n\
+            let o = Opt::Some(5);\n\
+            match o { Opt::Some(x) => print(x + 1), Opt::None => print(0) }\n\
+            }\n";
+        accepts(source);
+        let source = "enum Opt { Some(int), None }\n\
+            fn main() {\n\
+            let o = Opt::Some(5);\n\
+            match o { Opt::Some(x) => print(x), Opt::None => print(x) }\n\
+            }\n";
+        assert!(rejects(source).contains("cannot find value 'x'"));
+    }
+
+    #[test]
+    fn match_binding_has_payload_type() {
+        let source = "enum Msg { Text(string), Empty }\n\
+            fn main() {\n\
+            let m = Msg::Text(\"hi\");\n\
+            match m { Msg::Text(s) => { println(s + \"!\"); } Msg::Empty => {} }\n\
+            }\n";
+        accepts(source);
+    }
+
+    #[test]
+    fn option_is_predefined() {
+        accepts(&main_body("let a = Option::Some(5);\nlet b = Option::None;\nprint(a == b);\nprint(b == Option::None);"));
+    }
+
+    #[test]
+    fn string_match_patterns() {
+        accepts(&main_body("let s = \"b\";\nmatch s { \"a\" => print(1), _ => print(0) }"));
+        assert!(rejects(&main_body("let n = 5;\nmatch n { \"a\" => print(1), _ => print(0) }")).contains("found string pattern"));
+    }
+
+    #[test]
+    fn refutable_let_pattern_rejected() {
+        let source = "enum E { A(int), B }\nfn main() {\nlet E::A(x) = E::A(1);\nprintln(x);\n}\n";
+        assert!(rejects(source).contains("refutable pattern"));
+        accepts("enum W { Only(int) }\nfn main() {\nlet W::Only(x) = W::Only(1);\nprintln(x);\n}\n");
+    }
+
+    #[test]
+    fn if_let_binding_is_scoped_to_the_then_block() {
+        accepts("enum E { A(int), B }\nfn main() {\nlet e = E::A(1);\nif let E::A(x) = e {\nprintln(x);\n}\n}\n");
+        let source = "enum E { A(int), B }\nfn main() {\nlet e = E::A(1);\nif let E::A(x) = e {\nprintln(x);\n} else {\nprintln(x);\n}\n}\n";
+        assert!(rejects(source).contains("cannot find value 'x'"));
+    }
+
+    #[test]
+    fn question_mark_needs_a_result_function() {
+        let result = "enum Result { Ok(int), Err(int) }\n\
+            fn half(n: int) -> Result {\nif n % 2 == 1 {\nreturn Result::Err(n);\n}\nreturn Result::Ok(n / 2);\n}\n";
+        accepts(&format!("{}fn quarter(n: int) -> Result {{\nlet h = half(n)?;\nreturn half(h);\n}}\nfn main() {{\nlet q = quarter(8);\nprint(q == Result::Ok(2));\n}}\n", result));
+        assert!(rejects(&format!("{}fn main() {{\nlet h = half(4)?;\nprint(h);\n}}\n", result)).contains("'?' operator can only be used"));
+    }
+
+    #[test]
+    fn nested_functions_are_block_local() {
+        accepts("fn main() {\nfn helper() -> int {\nreturn 1;\n}\nprint(helper());\n}\n");
+        let source = "fn other() {\nfn helper() -> int {\nreturn 1;\n}\n}\nfn main() {\nprint(helper());\n}\n";
+        assert!(rejects(source).contains("cannot find function 'helper'"));
+    }
+
+    #[test]
+    fn main_may_take_its_arguments() {
+        accepts("fn main(args: [string]) -> int {\nprint(args[0]);\nprint(len(args));\nreturn 0;\n}\n");
+        assert!(rejects("fn main(n: int) {\nprint(n);\n}\n").contains("must take no parameters or a single 'args: [string]' parameter"));
+    }
+
+    #[test]
+    fn entry_signature_error_points_at_the_entry() {
+        let source = "fn helper() {\nlet x = 1;\nprintln(x);\n}\n\n  fn main(n: int) {\nprintln(n);\n}\n";
+        assert!(rejects(source).starts_with("test.brn:6:3: Error: entry function 'main' must take no parameters"));
+    }
+
+    #[test]
+    fn char_array_conversions() {
+        accepts(&main_body("let c = to_chars(\"hi\");\nprint(c[0]);\nprint(from_chars(c) == \"hi\");"));
+    }
+
+    #[test]
+    fn aliases_name_existing_types() {
+        accepts("enum Color { Red, Green }\nalias C = Color;\nfn main() {\nlet c: C = C::Red;\nprint(c == Color::Red);\n}\n");
+        assert!(rejects("alias N = Nope;\nfn main() {}\n").contains("cannot find type 'Nope'"));
+    }
+
+    #[test]
+    fn mutable_borrows_end_after_the_call() {
+        let bump = "fn bump(&mut n: int) {\nn = n + 1;\n}\n";
+        accepts(&format!("{}fn main() {{\nlet mut x = 1;\nbump(&mut x);\nbump(&mut x);\nx = 5;\nprint(x);\n}}\n", bump));
+        assert!(rejects(&format!("{}fn main() {{\nlet x = 1;\nbump(&mut x);\n}}\n", bump)).contains("cannot borrow 'x' as mutable"));
+    }
+
+    #[test]
+    fn enum_comparisons_need_matching_types() {
+        accepts("enum Color { Red, Green }\nfn main() {\nprint(Color::Red == Color::Green);\n}\n");
+        assert!(rejects("enum Color { Red, Green }\nfn main() {\nprint(Color::Red == 1);\n}\n").contains("cannot apply '==' to 'Color' and 'int'"));
+    }
+
+    #[test]
+    fn bench_target_must_be_a_function() {
+        accepts("fn noop() {}\nfn main() {\nlet t = bench(1000, \"noop\");\nprint(t);\n}\n");
+        assert!(rejects(&main_body("let t = bench(10, \"missing\");\nprint(t);")).contains("cannot bench 'missing'"));
+    }
+
+    #[test]
+    fn contains_and_index_of() {
+        accepts(&main_body("print([1, 2, 3].contains(2));\nprint([1, 2, 3].index_of(3));"));
+        assert!(rejects(&main_body("print([1, 2, 3].contains(\"a\"));")).contains("cannot search"));
+    }
+
+    #[test]
+    fn deprecated_calls_warn() {
+        let source = "@deprecated(\"use new_f\")\nfn old_f() {}\nfn new_f() {}\nfn main() {\nold_f();\nnew_f();\n}\n";
+        let warnings = accepts(source);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("call to deprecated function 'old_f'"));
+        assert!(warnings[0].contains("use new_f"));
+    }
+
+    #[test]
+    fn unused_variables_warn() {
+        let warnings = accepts(&main_body("let x = 1;\nlet _y = 2;"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("unused variable 'x'"));
+    }
+
+    #[test]
+    fn format_placeholder_count() {
+        accepts(&main_body("print(format(\"{:5} {:.2}\", 42, \"abc\"));"));
+        assert!(rejects(&main_body("print(format(\"{} {}\", 1));")).contains("2 placeholder(s) but 1 argument(s)"));
+    }
+
+    #[test]
+    fn format_precision_needs_a_float_or_text() {
+        accepts(&main_body("println(format(\"{:.2} {:.1}\", 3.14159, \"abc\"));"));
+        let error = rejects(&main_body("println(format(\"{:.2}\", 5));"));
+        assert!(error.contains("precision '.2' cannot be applied to 'int'"));
+    }
+}
@@ -0,0 +1,130 @@
+//! Drives the `astrallang` binary for the behaviour that lives in `main.rs`.
+//! Programs are only compiled to IR; linking depends on clang being installed
+//! and is not checked.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn compiler() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_astrallang"))
+}
+
+/// A fresh scratch directory for one test.
+fn scratch(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("astrallang-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `source` to `<dir>/<file>` and compiles it with `flags`, writing the
+/// IR next to it.
+fn compile(dir: &Path, file: &str, source: &str, flags: &[&str]) -> Output {
+    let input = dir.join(file);
+    fs::write(&input, source).unwrap();
+    compiler()
+        .args(flags)
+        .arg(&input)
+        .arg(dir.join("out"))
+        .output()
+        .unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn imported_functions_are_compiled() {
+    let dir = scratch("import");
+    fs::write(dir.join("helper.brn"), "fn double(n: int) -> int {\n    return n * 2;\n}\n").unwrap();
+    let output = compile(&dir, "main.brn", "import \"helper.brn\";\n\nfn main() {\n    print(double(21));\n}\n", &[]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let ir = fs::read_to_string(dir.join("out.ll")).unwrap();
+    assert!(ir.contains("define i64 @double("));
+    assert!(ir.contains("call i64 @double(i64 21)"));
+}
+
+#[test]
+fn cfg_flags_select_blocks() {
+    let dir = scratch("cfg");
+    let source = "fn main() {\n    if cfg(\"debug\") {\n        print(4242);\n    }\n}\n";
+    let output = compile(&dir, "cfg.brn", source, &["--cfg", "debug"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(dir.join("out.ll")).unwrap().contains("4242"));
+    compile(&dir, "cfg.brn", source, &[]);
+    assert!(!fs::read_to_string(dir.join("out.ll")).unwrap().contains("4242"));
+}
+
+#[test]
+fn max_errors_caps_the_report() {
+    let dir = scratch("max-errors");
+    let mut source = String::new();
+    for i in 0..50 {
+        source.push_str(&format!("fn f{}() {{\n    print(missing);\n}}\n", i));
+    }
+    source.push_str("fn main() {}\n");
+    let output = compile(&dir, "errors.brn", &source, &["--max-errors", "5"]);
+    assert!(!output.status.success());
+    let stderr = stderr(&output);
+    assert_eq!(stderr.matches(": Error: ").count(), 5);
+    assert!(stderr.contains("... and 45 more errors"));
+}
+
+#[test]
+fn stdin_errors_name_stdin() {
+    let dir = scratch("stdin");
+    let mut child = compiler()
+        .arg("--stdin")
+        .arg(dir.join("out"))
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"fn main() {\n    print(missing);\n}\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert!(stderr(&output).starts_with("<stdin>:2:"));
+}
+
+#[test]
+fn help_lists_only_real_flags() {
+    let output = compiler().arg("--help").output().unwrap();
+    let help = String::from_utf8_lossy(&output.stdout);
+    assert!(help.contains("--max-errors N"));
+    assert!(!help.contains("--run-android"));
+    let output = compiler().arg("--version").output().unwrap();
+    assert!(String::from_utf8_lossy(&output.stdout).contains(env!("CARGO_PKG_VERSION")));
+}
+
+#[test]
+fn custom_entry_function() {
+    let dir = scratch("entry");
+    let output = compile(&dir, "begin.brn", "fn begin() {\n    print(1);\n}\n", &["--entry", "begin"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(fs::read_to_string(dir.join("out.ll")).unwrap().contains("define i32 @main()"));
+
+    let output = compile(&dir, "begin.brn", "fn start() {}\n", &["--entry", "begin"]);
+    assert!(stderr(&output).contains("entry function 'begin' is not defined"));
+}
+
+#[test]
+fn no_semantic_skips_checking() {
+    let dir = scratch("no-semantic");
+    let source = "fn main() {\n    let s = \"a\";\n    let t = s;\n    print(s);\n}\n";
+    assert!(!compile(&dir, "moved.brn", source, &[]).status.success());
+    assert!(compile(&dir, "moved.brn", source, &["--no-semantic"]).status.success());
+}
+
+#[test]
+fn strict_flag_reaches_the_analyzer() {
+    let dir = scratch("strict");
+    let source = "fn main() {\n    let n = 1;\n    if n {\n        println(n);\n    }\n}\n";
+    assert!(compile(&dir, "condition.brn", source, &[]).status.success());
+    let output = compile(&dir, "condition.brn", source, &["--strict"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("condition.brn:3:"));
+}